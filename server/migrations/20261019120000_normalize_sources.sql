@@ -0,0 +1,16 @@
+-- Normalize existing sources the same way new ones are stored
+UPDATE images SET source = NULL WHERE TRIM(source) = '';
+UPDATE images SET source = TRIM(source) WHERE source IS NOT NULL;
+UPDATE images SET source = CONCAT('https://', SUBSTRING(source, 8)) WHERE source LIKE 'http://%';
+UPDATE images SET source = CONCAT('https://', SUBSTRING(source, 13)) WHERE source LIKE 'https://www.%';
+UPDATE images SET source = TRIM(TRAILING '/' FROM source) WHERE source LIKE 'https://%';
+
+-- e926 is a mirror of e621
+UPDATE images SET source = CONCAT('https://e621.net', SUBSTRING(source, 17)) WHERE source LIKE 'https://e926.net%';
+UPDATE images SET source = CONCAT('https://e621.net/posts/', SUBSTRING_INDEX(SUBSTRING(source, 28), '/', 1)) WHERE source LIKE 'https://e621.net/post/show/%';
+UPDATE images SET source = SUBSTRING_INDEX(source, '?', 1) WHERE source LIKE 'https://e621.net/posts/%';
+
+-- twitter mirrors
+UPDATE images SET source = CONCAT('https://twitter.com', SUBSTRING(source, 14)) WHERE source LIKE 'https://x.com/%';
+UPDATE images SET source = CONCAT('https://twitter.com', SUBSTRING(source, 27)) WHERE source LIKE 'https://mobile.twitter.com/%';
+UPDATE images SET source = SUBSTRING_INDEX(source, '?', 1) WHERE source LIKE 'https://twitter.com/%';
@@ -0,0 +1,3 @@
+-- Add rating column, using the same codes as Shimmie ('s', 'q', 'e', or '?' for unrated)
+ALTER TABLE images ADD rating CHAR(1) NOT NULL DEFAULT '?';
+CREATE INDEX images_rating_idx ON images (rating);
@@ -0,0 +1,15 @@
+-- Records every change to a post's tags, so edits can be reviewed and reverted
+CREATE TABLE IF NOT EXISTS `post_tag_history` (
+	`id` int(11) NOT NULL AUTO_INCREMENT,
+	`image_id` int(11) NOT NULL,
+	`user_id` int(11) DEFAULT NULL,
+	`date` timestamp NOT NULL DEFAULT current_timestamp(),
+	`tags` text NOT NULL,
+	`added` text NOT NULL,
+	`removed` text NOT NULL,
+	PRIMARY KEY (`id`),
+	KEY `post_tag_history_image_id` (`image_id`, `id`),
+	KEY `post_tag_history_user_id` (`user_id`, `id`),
+	CONSTRAINT `post_tag_history_image_id_fk` FOREIGN KEY (`image_id`) REFERENCES `images` (`id`) ON DELETE CASCADE,
+	CONSTRAINT `post_tag_history_user_id_fk` FOREIGN KEY (`user_id`) REFERENCES `users` (`id`) ON DELETE SET NULL
+);
@@ -0,0 +1,16 @@
+-- Long running operations started from the API, tracked so their progress can be polled
+CREATE TABLE IF NOT EXISTS `background_jobs` (
+	`id` int(11) NOT NULL AUTO_INCREMENT,
+	`kind` varchar(64) NOT NULL,
+	`user_id` int(11) DEFAULT NULL,
+	`state` varchar(16) NOT NULL DEFAULT 'pending',
+	`progress` int(11) NOT NULL DEFAULT 0,
+	`total` int(11) NOT NULL DEFAULT 0,
+	`data` text NOT NULL,
+	`result` text DEFAULT NULL,
+	`created_at` timestamp NOT NULL DEFAULT current_timestamp(),
+	`updated_at` timestamp NOT NULL DEFAULT current_timestamp() ON UPDATE current_timestamp(),
+	PRIMARY KEY (`id`),
+	KEY `background_jobs_user_id` (`user_id`, `id`),
+	CONSTRAINT `background_jobs_user_id_fk` FOREIGN KEY (`user_id`) REFERENCES `users` (`id`) ON DELETE SET NULL
+);
@@ -0,0 +1,2 @@
+-- Lets jobs record how far they got, so they can pick up where they left off after a restart
+ALTER TABLE background_jobs ADD `cursor` bigint(20) DEFAULT NULL;
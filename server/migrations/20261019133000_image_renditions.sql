@@ -0,0 +1,11 @@
+-- Downscaled copies of each post, in every format they were encoded in
+CREATE TABLE IF NOT EXISTS `image_renditions` (
+	`image_id` int(11) NOT NULL,
+	`rendition` varchar(16) NOT NULL,
+	`format` varchar(8) NOT NULL,
+	`width` int(11) NOT NULL,
+	`height` int(11) NOT NULL,
+	`filesize` int(11) NOT NULL,
+	PRIMARY KEY (`image_id`, `rendition`, `format`),
+	CONSTRAINT `image_renditions_image_id_fk` FOREIGN KEY (`image_id`) REFERENCES `images` (`id`) ON DELETE CASCADE
+);
@@ -0,0 +1,5 @@
+-- Layout of storyboard sprite sheets, NULL for every other rendition
+ALTER TABLE `image_renditions`
+	ADD `storyboard_frames` int(11) UNSIGNED DEFAULT NULL,
+	ADD `storyboard_columns` int(11) UNSIGNED DEFAULT NULL,
+	ADD `storyboard_interval` int(11) UNSIGNED DEFAULT NULL;
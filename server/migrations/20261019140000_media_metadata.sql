@@ -0,0 +1,4 @@
+-- More media information from ffprobe, filled in on upload or by the media info backfill job
+ALTER TABLE `images`
+	ADD `frame_rate` float DEFAULT NULL,
+	ADD `bit_depth` tinyint(3) UNSIGNED DEFAULT NULL;
@@ -0,0 +1,4 @@
+-- Hash of the file as uploaded, before its metadata was stripped, so re-uploads of it are still caught
+ALTER TABLE `images`
+	ADD `original_hash` char(32) DEFAULT NULL,
+	ADD KEY `images_original_hash` (`original_hash`);
@@ -0,0 +1,15 @@
+-- Long-lived keys for scripts and bots, stored as SHA-256 hashes
+CREATE TABLE IF NOT EXISTS `user_api_keys` (
+	`id` int(11) NOT NULL AUTO_INCREMENT,
+	`user_id` int(11) NOT NULL,
+	`name` varchar(64) NOT NULL,
+	`prefix` varchar(16) NOT NULL,
+	`key_hash` char(64) NOT NULL,
+	`scopes` varchar(255) NOT NULL,
+	`created_at` timestamp NOT NULL DEFAULT current_timestamp(),
+	`last_used_at` timestamp NULL DEFAULT NULL,
+	PRIMARY KEY (`id`),
+	UNIQUE KEY `user_api_keys_key_hash` (`key_hash`),
+	KEY `user_api_keys_user_id` (`user_id`),
+	CONSTRAINT `user_api_keys_user_id_fk` FOREIGN KEY (`user_id`) REFERENCES `users` (`id`) ON DELETE CASCADE
+);
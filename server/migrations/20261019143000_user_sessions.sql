@@ -0,0 +1,16 @@
+-- Every login is a session, so its tokens can be listed and revoked
+CREATE TABLE IF NOT EXISTS `user_sessions` (
+	`id` int(11) NOT NULL AUTO_INCREMENT,
+	`user_id` int(11) NOT NULL,
+	`token_id` char(32) NOT NULL,
+	`user_agent` varchar(255) DEFAULT NULL,
+	`ip` varchar(45) DEFAULT NULL,
+	`created_at` timestamp NOT NULL DEFAULT current_timestamp(),
+	`last_used_at` timestamp NOT NULL DEFAULT current_timestamp(),
+	`expires_at` timestamp NOT NULL,
+	PRIMARY KEY (`id`),
+	UNIQUE KEY `user_sessions_token_id` (`token_id`),
+	KEY `user_sessions_user_id` (`user_id`),
+	KEY `user_sessions_expires_at` (`expires_at`),
+	CONSTRAINT `user_sessions_user_id_fk` FOREIGN KEY (`user_id`) REFERENCES `users` (`id`) ON DELETE CASCADE
+);
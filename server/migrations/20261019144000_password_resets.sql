@@ -0,0 +1,12 @@
+-- Single-use tokens for resetting a forgotten password, stored as SHA-256 hashes
+CREATE TABLE IF NOT EXISTS `password_resets` (
+	`id` int(11) NOT NULL AUTO_INCREMENT,
+	`user_id` int(11) NOT NULL,
+	`token_hash` char(64) NOT NULL,
+	`created_at` timestamp NOT NULL DEFAULT current_timestamp(),
+	`expires_at` timestamp NOT NULL,
+	PRIMARY KEY (`id`),
+	UNIQUE KEY `password_resets_token_hash` (`token_hash`),
+	KEY `password_resets_user_id` (`user_id`),
+	CONSTRAINT `password_resets_user_id_fk` FOREIGN KEY (`user_id`) REFERENCES `users` (`id`) ON DELETE CASCADE
+);
@@ -0,0 +1,30 @@
+-- User classes and the permissions each of them has
+CREATE TABLE IF NOT EXISTS `user_classes` (
+	`name` varchar(32) NOT NULL,
+	`description` varchar(255) NOT NULL DEFAULT '',
+	PRIMARY KEY (`name`)
+);
+
+CREATE TABLE IF NOT EXISTS `user_class_permissions` (
+	`class` varchar(32) NOT NULL,
+	`permission` varchar(32) NOT NULL,
+	PRIMARY KEY (`class`, `permission`),
+	CONSTRAINT `user_class_permissions_class_fk` FOREIGN KEY (`class`) REFERENCES `user_classes` (`name`) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+INSERT IGNORE INTO `user_classes` (`name`, `description`) VALUES
+	('admin', 'Can do everything'),
+	('user', 'Regular users');
+
+-- any other classes already given out start without permissions
+INSERT IGNORE INTO `user_classes` (`name`) SELECT DISTINCT `class` FROM `users`;
+
+-- admins keep everything they could do before permissions existed
+INSERT IGNORE INTO `user_class_permissions` (`class`, `permission`) VALUES
+	('admin', 'delete_post'),
+	('admin', 'lock_post'),
+	('admin', 'edit_any_pool'),
+	('admin', 'manage_tags'),
+	('admin', 'manage_users'),
+	('admin', 'manage_classes'),
+	('admin', 'manage_system');
@@ -0,0 +1,14 @@
+-- Bans are kept after they end or are lifted, as a record of the account's history
+CREATE TABLE IF NOT EXISTS `user_bans` (
+	`id` int(11) NOT NULL AUTO_INCREMENT,
+	`user_id` int(11) NOT NULL,
+	`banned_by` int(11) DEFAULT NULL,
+	`reason` varchar(255) NOT NULL DEFAULT '',
+	`created_at` timestamp NOT NULL DEFAULT current_timestamp(),
+	`expires_at` timestamp NULL DEFAULT NULL,
+	`lifted_at` timestamp NULL DEFAULT NULL,
+	PRIMARY KEY (`id`),
+	KEY `user_bans_user_id` (`user_id`, `id`),
+	CONSTRAINT `user_bans_user_id_fk` FOREIGN KEY (`user_id`) REFERENCES `users` (`id`) ON DELETE CASCADE,
+	CONSTRAINT `user_bans_banned_by_fk` FOREIGN KEY (`banned_by`) REFERENCES `users` (`id`) ON DELETE SET NULL
+);
@@ -0,0 +1,3 @@
+-- Mass edits used to be open to every user, now only classes given this permission can run them
+INSERT IGNORE INTO `user_class_permissions` (`class`, `permission`) VALUES
+	('admin', 'mass_edit_posts');
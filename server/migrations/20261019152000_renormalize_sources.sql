@@ -0,0 +1,4 @@
+-- The SQL rules in 20261019120000_normalize_sources don't match normalize_source: they miss several
+-- mirror hosts, scheme-less URLs and host lowercasing, and trim trailing slashes from queries.
+-- Queue the source normalization job so it redoes every source once the server starts.
+INSERT INTO background_jobs (`kind`, `user_id`, `data`) VALUES ('normalize_sources', NULL, 'null');
@@ -7,13 +7,19 @@ use sqlx::{
 };
 use std::{str::FromStr, sync::Arc};
 
-use crate::{booru_config::BooruConfig, error::AppError, storage::AppStorage};
+use crate::{
+    booru_config::BooruConfig,
+    error::AppError,
+    mailer::{create_mailer, AppMailer},
+    storage::{create_storage, AppStorage},
+};
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: MySqlPool,
     pub config: Config,
     pub storage: Arc<AppStorage>,
+    pub mailer: Arc<AppMailer>,
     pub booru_config: BooruConfig,
 }
 
@@ -51,13 +57,19 @@ impl AppState {
             .build()
             .map_err(|e| AppError::Message("failed to create config".to_owned()))?;
 
-        let storage = AppStorage::new(&config).await;
+        let storage = create_storage(&config)
+            .await
+            .map_err(|e| AppError::Message(format!("Failed to create storage: {}", e)))?;
+        let mailer = create_mailer(&config)
+            .await
+            .map_err(|e| AppError::Message(format!("Failed to create mailer: {}", e)))?;
         let booru_config = BooruConfig::new(&pool.clone()).await;
 
         Ok(AppState {
             db: pool,
             config,
-            storage: Arc::new(storage),
+            storage,
+            mailer,
             booru_config,
         })
     }
@@ -2,6 +2,8 @@ use std::collections::HashMap;
 
 use sqlx::MySqlPool;
 
+use crate::storage::RenditionFormat;
+
 #[derive(Clone)]
 pub enum ThumbnailFit {
     Fit,
@@ -31,7 +33,19 @@ pub struct BooruConfig {
     pub thumb_width: u32,
     pub thumb_height: u32,
     pub thumb_fit: ThumbnailFit,
+    /// Format renditions are encoded in, next to their JPEG fallback
+    pub rendition_format: RenditionFormat,
+    /// Longest side of preview renditions, images smaller than this don't get one
+    pub preview_size: u32,
+    /// Longest side of sample renditions, images smaller than this don't get one
+    pub sample_size: u32,
+    /// Length of video preview clips in seconds, 0 turns them off
+    pub clip_length: u32,
     pub signup_requires_invite: bool,
+    /// Remove EXIF, GPS and other identifying metadata from uploaded images
+    pub strip_metadata: bool,
+    /// How long access tokens last in seconds, before they have to be refreshed
+    pub access_token_ttl: i64,
 }
 
 impl BooruConfig {
@@ -74,7 +88,7 @@ impl BooruConfig {
                 .and_then(|s| s.parse::<u32>().ok())
                 .unwrap_or(192),
             thumb_height: config
-                .get("thumb_width")
+                .get("thumb_height")
                 .and_then(|s| s.parse::<u32>().ok())
                 .unwrap_or(192),
             thumb_fit: config
@@ -82,10 +96,37 @@ impl BooruConfig {
                 .unwrap_or(&"".to_owned())
                 .clone()
                 .into(),
+            rendition_format: config
+                .get("rendition_format")
+                .and_then(|s| RenditionFormat::parse(s))
+                // a JPEG is always made, so it would only be encoded twice
+                .filter(|f| *f == RenditionFormat::Webp || *f == RenditionFormat::Avif)
+                .unwrap_or(RenditionFormat::Webp),
+            preview_size: config
+                .get("preview_size")
+                .and_then(|s| s.parse::<u32>().ok())
+                .unwrap_or(1280),
+            sample_size: config
+                .get("sample_size")
+                .and_then(|s| s.parse::<u32>().ok())
+                .unwrap_or(2560),
+            clip_length: config
+                .get("clip_length")
+                .and_then(|s| s.parse::<u32>().ok())
+                .unwrap_or(3),
             signup_requires_invite: config
                 .get("signup_requires_invite")
                 .and_then(|s| Some(s == "Y"))
                 .unwrap_or(true),
+            strip_metadata: config
+                .get("strip_metadata")
+                .and_then(|s| Some(s == "Y"))
+                .unwrap_or(true),
+            access_token_ttl: config
+                .get("access_token_ttl")
+                .and_then(|s| s.parse::<i64>().ok())
+                .filter(|ttl| *ttl > 0)
+                .unwrap_or(60 * 60 * 24),
         }
     }
 }
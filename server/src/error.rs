@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 
+use super::storage::StorageError;
 use super::util::{error_response, success_response};
 use actix_web::{http::StatusCode, HttpResponse};
 use derive_more::Display;
@@ -21,6 +22,8 @@ pub enum ApiErrorType {
     InvalidRequest,
     ServerError,
     OperationFailed,
+    /// The request was based on data that has since changed
+    Conflict,
 }
 
 #[derive(Debug, Display, Error, Clone, Serialize, Deserialize)]
@@ -28,6 +31,9 @@ pub enum ApiErrorType {
 pub struct ApiError {
     pub message: String,
     pub error_type: ApiErrorType,
+    /// Extra details for the client, like the current state of something that conflicted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
 }
 
 impl ApiError {
@@ -35,6 +41,7 @@ impl ApiError {
         ApiError {
             error_type,
             message,
+            data: None,
         }
     }
 }
@@ -46,6 +53,13 @@ impl From<S3Error> for ApiError {
     }
 }
 
+impl From<StorageError> for ApiError {
+    fn from(value: StorageError) -> Self {
+        error!("Storage error: {:?}", value);
+        api_error(ApiErrorType::ServerError, "Unknown storage error")
+    }
+}
+
 impl From<sqlx::Error> for ApiError {
     fn from(value: sqlx::Error) -> Self {
         error!("Database error: {:?}", value);
@@ -80,7 +94,16 @@ impl From<sqlx::Error> for ApiKeyedError {
 
 impl actix_web::error::ResponseError for ApiError {
     fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
-        HttpResponse::build(self.status_code()).json(error_response(self.message.clone()))
+        match &self.data {
+            Some(data) => HttpResponse::build(self.status_code()).json(json!({
+                "type": "error",
+                "message": self.message.clone(),
+                "data": data.clone()
+            })),
+            None => {
+                HttpResponse::build(self.status_code()).json(error_response(self.message.clone()))
+            }
+        }
     }
 
     fn status_code(&self) -> StatusCode {
@@ -90,6 +113,7 @@ impl actix_web::error::ResponseError for ApiError {
             ApiErrorType::ServerError => StatusCode::INTERNAL_SERVER_ERROR,
             ApiErrorType::Forbidden => StatusCode::FORBIDDEN,
             ApiErrorType::OperationFailed => StatusCode::OK,
+            ApiErrorType::Conflict => StatusCode::CONFLICT,
         }
     }
 }
@@ -107,6 +131,7 @@ pub fn api_error(error_type: ApiErrorType, message: &'static str) -> ApiError {
     ApiError {
         error_type,
         message: message.to_owned(),
+        data: None,
     }
 }
 
@@ -114,6 +139,19 @@ pub fn api_error_owned(error_type: ApiErrorType, message: String) -> ApiError {
     ApiError {
         message,
         error_type,
+        data: None,
+    }
+}
+
+pub fn api_error_with_data(
+    error_type: ApiErrorType,
+    message: &'static str,
+    data: impl Serialize,
+) -> ApiError {
+    ApiError {
+        error_type,
+        message: message.to_owned(),
+        data: serde_json::to_value(data).ok(),
     }
 }
 
@@ -0,0 +1,44 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use uuid::Uuid;
+
+use super::{Mail, MailerBackend, MailerError};
+
+/// Writes each email to its own file in a directory, so they can be read by tests or offline setups.
+pub struct FileMailer {
+    dir: PathBuf,
+}
+
+impl FileMailer {
+    pub async fn new(config: &config::Config) -> Result<FileMailer, MailerError> {
+        let dir = PathBuf::from(
+            config
+                .get_string("mail_file_path")
+                .unwrap_or("data/mail".to_owned()),
+        );
+        tokio::fs::create_dir_all(&dir).await?;
+
+        Ok(FileMailer { dir })
+    }
+}
+
+#[async_trait]
+impl MailerBackend for FileMailer {
+    async fn send(&self, mail: &Mail) -> Result<(), MailerError> {
+        // sorts by when the email was sent
+        let name = format!(
+            "{}-{}.txt",
+            Utc::now().format("%Y%m%d%H%M%S%3f"),
+            Uuid::new_v4().simple()
+        );
+        let contents = format!(
+            "To: {}\nSubject: {}\n\n{}\n",
+            mail.to, mail.subject, mail.body
+        );
+
+        tokio::fs::write(self.dir.join(name), contents).await?;
+        Ok(())
+    }
+}
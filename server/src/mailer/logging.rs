@@ -0,0 +1,18 @@
+use async_trait::async_trait;
+use log::info;
+
+use super::{Mail, MailerBackend, MailerError};
+
+/// Writes emails to the log instead of sending them, for development.
+pub struct LogMailer {}
+
+#[async_trait]
+impl MailerBackend for LogMailer {
+    async fn send(&self, mail: &Mail) -> Result<(), MailerError> {
+        info!(
+            "Email to {}\nSubject: {}\n\n{}",
+            mail.to, mail.subject, mail.body
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use thiserror::Error;
+
+mod file;
+mod logging;
+mod smtp;
+
+pub use file::FileMailer;
+pub use logging::LogMailer;
+pub use smtp::SmtpMailer;
+
+#[derive(Debug, Error)]
+pub enum MailerError {
+    #[error("SMTP error: {0}")]
+    Smtp(#[from] lettre::transport::smtp::Error),
+    #[error("failed to build message: {0}")]
+    Message(#[from] lettre::error::Error),
+    #[error("invalid address: {0}")]
+    Address(#[from] lettre::address::AddressError),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("mailer misconfigured: {0}")]
+    Config(String),
+}
+
+/// A plain text email.
+#[derive(Debug, Clone)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[async_trait]
+pub trait MailerBackend: Send + Sync {
+    async fn send(&self, mail: &Mail) -> Result<(), MailerError>;
+}
+
+pub type AppMailer = dyn MailerBackend;
+
+pub async fn create_mailer(config: &config::Config) -> Result<Arc<AppMailer>, MailerError> {
+    let backend = config.get_string("mailer").unwrap_or("log".to_owned());
+
+    let mailer: Arc<AppMailer> = match backend.as_str() {
+        "smtp" => Arc::new(SmtpMailer::new(config)?),
+        "file" => Arc::new(FileMailer::new(config).await?),
+        "log" => Arc::new(LogMailer {}),
+        _ => return Err(MailerError::Config(format!("unknown mailer '{}'", backend))),
+    };
+
+    Ok(mailer)
+}
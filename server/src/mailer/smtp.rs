@@ -0,0 +1,55 @@
+use async_trait::async_trait;
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+
+use super::{Mail, MailerBackend, MailerError};
+
+/// Sends emails through an SMTP server, using STARTTLS.
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn new(config: &config::Config) -> Result<SmtpMailer, MailerError> {
+        let host = config
+            .get_string("smtp_host")
+            .map_err(|_| MailerError::Config("smtp_host must be set".to_owned()))?;
+        let from = config
+            .get_string("mail_from")
+            .map_err(|_| MailerError::Config("mail_from must be set".to_owned()))?
+            .parse::<Mailbox>()?;
+
+        let mut transport = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host)?;
+        if let Ok(port) = config.get_int("smtp_port") {
+            transport = transport.port(port as u16);
+        }
+        if let Ok(username) = config.get_string("smtp_username") {
+            let password = std::env::var("SMTP_PASSWORD").unwrap_or_default();
+            transport = transport.credentials(Credentials::new(username, password));
+        }
+
+        Ok(SmtpMailer {
+            transport: transport.build(),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl MailerBackend for SmtpMailer {
+    async fn send(&self, mail: &Mail) -> Result<(), MailerError> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(mail.to.parse::<Mailbox>()?)
+            .subject(mail.subject.clone())
+            .header(ContentType::TEXT_PLAIN)
+            .body(mail.body.clone())?;
+
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
@@ -14,6 +14,7 @@ use util::error_response;
 mod app_state;
 mod booru_config;
 mod error;
+mod mailer;
 mod modules;
 mod storage;
 mod util;
@@ -31,7 +32,9 @@ fn configure(conf: &mut web::ServiceConfig) {
         .service(modules::import::scope())
         .service(modules::posts::scope())
         .service(modules::system::scope())
-        .service(modules::pools::scope());
+        .service(modules::pools::scope())
+        .service(modules::jobs::scope())
+        .service(modules::admin::scope());
 
     conf.service(scope)
         .default_service(web::route().to(not_found));
@@ -40,6 +43,7 @@ fn configure(conf: &mut web::ServiceConfig) {
 #[actix_web::main]
 async fn main() -> Result<(), anyhow::Error> {
     let state = AppState::initialize().await?;
+    modules::jobs::resume_jobs(&state).await?;
 
     let port: i64 = state.config.get_int("port").unwrap_or(8121);
     let _development_mode: bool = state.config.get_bool("development_mode").unwrap_or(true);
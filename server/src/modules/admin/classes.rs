@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use sqlx::MySqlPool;
+
+use crate::{
+    error::{api_error, api_success, ApiError, ApiErrorType},
+    modules::users::{
+        middleware::{get_user, AuthFactory},
+        permissions::{require_permission, Permission, DEFAULT_CLASS},
+    },
+    AppState,
+};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UserClassResponse {
+    pub name: String,
+    pub description: String,
+    pub permissions: Vec<Permission>,
+    /// Number of users in the class
+    pub users: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UserClassListResponse {
+    pub classes: Vec<UserClassResponse>,
+    /// Every permission a class can be given
+    pub permissions: Vec<Permission>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UserClassSetSchema {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub permissions: Vec<Permission>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UserClassDeleteSchema {
+    pub name: String,
+    /// Class to move the deleted class's users to
+    pub replacement: Option<String>,
+}
+
+async fn list_classes(db: &MySqlPool) -> Result<UserClassListResponse, ApiError> {
+    let classes = sqlx::query_as::<_, (String, String, i64)>(
+        "SELECT c.name, c.description, (SELECT COUNT(*) FROM users AS u WHERE u.class = c.name) FROM user_classes AS c ORDER BY c.name ASC",
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut permissions: HashMap<String, Vec<Permission>> = HashMap::new();
+    for (class, permission) in sqlx::query_as::<_, (String, String)>(
+        "SELECT class, permission FROM user_class_permissions",
+    )
+    .fetch_all(db)
+    .await?
+    {
+        // skip permissions that have been removed from the code
+        if let Some(permission) = Permission::parse(&permission) {
+            permissions.entry(class).or_default().push(permission);
+        }
+    }
+
+    Ok(UserClassListResponse {
+        classes: classes
+            .into_iter()
+            .map(|(name, description, users)| UserClassResponse {
+                permissions: permissions.remove(&name).unwrap_or_default(),
+                name,
+                description,
+                users,
+            })
+            .collect(),
+        permissions: Permission::ALL.to_vec(),
+    })
+}
+
+#[get("/class/list", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn admin_class_list_handler(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+
+    require_permission(&data.db, &user, Permission::ManageClasses).await?;
+
+    Ok(api_success(list_classes(&data.db).await?))
+}
+
+#[post("/class/set", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn admin_class_set_handler(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    body: web::Json<UserClassSetSchema>,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+
+    require_permission(&data.db, &user, Permission::ManageClasses).await?;
+
+    let name = body.name.trim();
+    if name.is_empty() || name.chars().count() > 32 {
+        return Err(api_error(
+            ApiErrorType::InvalidRequest,
+            "Class names must be between 1 and 32 characters",
+        ));
+    }
+    if body.description.chars().count() > 255 {
+        return Err(api_error(
+            ApiErrorType::InvalidRequest,
+            "Class descriptions can be at most 255 characters",
+        ));
+    }
+
+    // otherwise nobody might be left who can give it back
+    if name == user.class && !body.permissions.contains(&Permission::ManageClasses) {
+        return Err(api_error(
+            ApiErrorType::InvalidRequest,
+            "Can't remove manage_classes from your own class",
+        ));
+    }
+
+    let mut transaction = data.db.begin().await?;
+
+    sqlx::query("INSERT INTO user_classes (name, description) VALUES (?, ?) ON DUPLICATE KEY UPDATE description = VALUES(description)")
+        .bind(name)
+        .bind(&body.description)
+        .execute(&mut *transaction)
+        .await?;
+
+    sqlx::query("DELETE FROM user_class_permissions WHERE class = ?")
+        .bind(name)
+        .execute(&mut *transaction)
+        .await?;
+
+    for permission in body.permissions.iter().unique_by(|p| p.name()) {
+        sqlx::query("INSERT INTO user_class_permissions (class, permission) VALUES (?, ?)")
+            .bind(name)
+            .bind(permission.name())
+            .execute(&mut *transaction)
+            .await?;
+    }
+
+    transaction.commit().await?;
+
+    Ok(api_success(list_classes(&data.db).await?))
+}
+
+#[post("/class/delete", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn admin_class_delete_handler(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    body: web::Json<UserClassDeleteSchema>,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+
+    require_permission(&data.db, &user, Permission::ManageClasses).await?;
+
+    if body.name == DEFAULT_CLASS || body.name == user.class {
+        return Err(api_error(
+            ApiErrorType::InvalidRequest,
+            "Can't delete the default class or your own class",
+        ));
+    }
+
+    let mut transaction = data.db.begin().await?;
+
+    let (users,) = sqlx::query_as::<_, (i64,)>("SELECT COUNT(*) FROM users WHERE class = ?")
+        .bind(&body.name)
+        .fetch_one(&mut *transaction)
+        .await?;
+
+    if users > 0 {
+        let Some(replacement) = body.replacement.as_ref().filter(|r| **r != body.name) else {
+            return Err(api_error(
+                ApiErrorType::InvalidRequest,
+                "Class still has users, give a replacement class to move them to",
+            ));
+        };
+
+        let (exists,) =
+            sqlx::query_as::<_, (i64,)>("SELECT COUNT(*) FROM user_classes WHERE name = ?")
+                .bind(replacement)
+                .fetch_one(&mut *transaction)
+                .await?;
+        if exists < 1 {
+            return Err(api_error(
+                ApiErrorType::InvalidRequest,
+                "Replacement class doesn't exist",
+            ));
+        }
+
+        sqlx::query("UPDATE users SET class = ? WHERE class = ?")
+            .bind(replacement)
+            .bind(&body.name)
+            .execute(&mut *transaction)
+            .await?;
+    }
+
+    sqlx::query("DELETE FROM user_classes WHERE name = ?")
+        .bind(&body.name)
+        .execute(&mut *transaction)
+        .await?;
+
+    transaction.commit().await?;
+
+    Ok(api_success(list_classes(&data.db).await?))
+}
@@ -0,0 +1,16 @@
+use actix_web::{web, Scope};
+
+mod classes;
+mod users;
+
+pub fn scope() -> Scope {
+    web::scope("/admin")
+        .service(classes::admin_class_list_handler)
+        .service(classes::admin_class_set_handler)
+        .service(classes::admin_class_delete_handler)
+        .service(users::admin_user_list_handler)
+        .service(users::admin_user_class_handler)
+        .service(users::admin_user_ban_handler)
+        .service(users::admin_user_unban_handler)
+        .service(users::admin_user_delete_handler)
+}
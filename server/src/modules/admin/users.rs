@@ -0,0 +1,461 @@
+use std::collections::HashMap;
+
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use sqlx::MySqlPool;
+
+use crate::{
+    error::{api_error, api_success, ApiError, ApiErrorType},
+    modules::users::{
+        bans::{UserBanModel, ACTIVE_BAN_CONDITION},
+        middleware::{get_user, AuthFactory},
+        model::{filter_db_record, UserModel, UserModelResponse},
+        permissions::{
+            has_permission, has_permissions_beyond, require_permission, Permission, DEFAULT_CLASS,
+        },
+        sessions::util::revoke_sessions,
+    },
+    AppState,
+};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AdminUserListSchema {
+    /// Only users whose name or email contains this
+    pub search: Option<String>,
+    pub class: Option<String>,
+    /// Only users who are, or aren't, currently banned
+    pub banned: Option<bool>,
+    pub limit: Option<i32>,
+    pub offset: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AdminUserResponse {
+    #[serde(flatten)]
+    pub user: UserModelResponse,
+    /// The ban currently in effect, if any
+    pub ban: Option<UserBanModel>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AdminUserListResponse {
+    pub users: Vec<AdminUserResponse>,
+    /// Number of users matching the filters, ignoring limit and offset
+    pub count: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AdminUserClassSchema {
+    pub user_id: i32,
+    pub class: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AdminUserBanSchema {
+    pub user_id: i32,
+    #[serde(default)]
+    pub reason: String,
+    /// When the ban ends, or missing for a permanent ban
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AdminUserUnbanSchema {
+    pub user_id: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AdminUserDeleteMode {
+    /// Remove the account entirely
+    Delete,
+    /// Keep the account so their posts stay attributed to it, but remove everything identifying about it
+    Anonymize,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AdminUserDeleteSchema {
+    pub user_id: i32,
+    pub mode: AdminUserDeleteMode,
+    /// User to give the account's posts and pools to. Needed to delete accounts that have any
+    pub reassign_to: Option<i32>,
+}
+
+async fn fetch_admin_users(
+    db: &MySqlPool,
+    users: Vec<UserModel>,
+) -> Result<Vec<AdminUserResponse>, ApiError> {
+    let mut bans: HashMap<i32, UserBanModel> = HashMap::new();
+    if !users.is_empty() {
+        let query = format!(
+            "SELECT id, user_id, banned_by, reason, created_at, expires_at FROM user_bans WHERE user_id IN ({}) AND {} ORDER BY id ASC",
+            users.iter().map(|u| u.id.to_string()).join(","),
+            ACTIVE_BAN_CONDITION
+        );
+        // later bans replace earlier ones, so the latest is kept
+        for ban in sqlx::query_as::<_, UserBanModel>(query.as_str())
+            .fetch_all(db)
+            .await?
+        {
+            bans.insert(ban.user_id, ban);
+        }
+    }
+
+    Ok(users
+        .iter()
+        .map(|user| AdminUserResponse {
+            user: filter_db_record(user),
+            ban: bans.remove(&user.id),
+        })
+        .collect())
+}
+
+async fn fetch_admin_user(db: &MySqlPool, user_id: i32) -> Result<AdminUserResponse, ApiError> {
+    let user = sqlx::query_as::<_, UserModel>("SELECT * FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_optional(db)
+        .await?
+        .ok_or(api_error(ApiErrorType::InvalidRequest, "User not found"))?;
+
+    Ok(fetch_admin_users(db, vec![user])
+        .await?
+        .pop()
+        .expect("No user?"))
+}
+
+/// Makes sure an admin isn't locking themselves out.
+fn check_not_self(user: &UserModel, user_id: i32) -> Result<(), ApiError> {
+    if user.id == user_id {
+        return Err(api_error(
+            ApiErrorType::InvalidRequest,
+            "Can't do this to your own account",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Looks up the user an admin action is aimed at, making sure it isn't the admin themselves
+/// and that the user's class can't do anything the admin's class can't.
+async fn fetch_target_user(
+    db: &MySqlPool,
+    user: &UserModel,
+    user_id: i32,
+) -> Result<UserModel, ApiError> {
+    check_not_self(user, user_id)?;
+
+    let target = sqlx::query_as::<_, UserModel>("SELECT * FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_optional(db)
+        .await?
+        .ok_or(api_error(ApiErrorType::InvalidRequest, "User not found"))?;
+
+    if has_permissions_beyond(db, &target.class, &user.class).await? {
+        return Err(api_error(
+            ApiErrorType::Forbidden,
+            "User's class has permissions your class doesn't",
+        ));
+    }
+
+    Ok(target)
+}
+
+#[get("/user/list", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn admin_user_list_handler(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    body: web::Query<AdminUserListSchema>,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+
+    require_permission(&data.db, &user, Permission::ManageUsers).await?;
+
+    let limit = body.limit.unwrap_or(50).clamp(1, 200);
+    let offset = body.offset.unwrap_or(0).max(0);
+
+    let mut conditions: Vec<String> = vec!["1".to_owned()];
+    let mut binds: Vec<String> = Vec::new();
+    if let Some(search) = body.search.as_ref().filter(|s| !s.trim().is_empty()) {
+        let pattern = format!(
+            "%{}%",
+            search
+                .trim()
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        );
+        conditions.push("(name LIKE ? OR email LIKE ?)".to_owned());
+        binds.push(pattern.clone());
+        binds.push(pattern);
+    }
+    if let Some(class) = &body.class {
+        conditions.push("class = ?".to_owned());
+        binds.push(class.clone());
+    }
+    if let Some(banned) = body.banned {
+        conditions.push(format!(
+            "{}EXISTS (SELECT 1 FROM user_bans WHERE user_bans.user_id = users.id AND {})",
+            if banned { "" } else { "NOT " },
+            ACTIVE_BAN_CONDITION
+        ));
+    }
+    let where_clause = conditions.join(" AND ");
+
+    let list_query = format!(
+        "SELECT * FROM users WHERE {} ORDER BY id ASC LIMIT ? OFFSET ?",
+        where_clause
+    );
+    let mut query = sqlx::query_as::<_, UserModel>(list_query.as_str());
+    for bind in &binds {
+        query = query.bind(bind);
+    }
+    let users = query.bind(limit).bind(offset).fetch_all(&data.db).await?;
+
+    let count_query = format!("SELECT COUNT(*) FROM users WHERE {}", where_clause);
+    let mut query = sqlx::query_as::<_, (i64,)>(count_query.as_str());
+    for bind in &binds {
+        query = query.bind(bind);
+    }
+    let (count,) = query.fetch_one(&data.db).await?;
+
+    Ok(api_success(AdminUserListResponse {
+        users: fetch_admin_users(&data.db, users).await?,
+        count,
+    }))
+}
+
+#[post("/user/class", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn admin_user_class_handler(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    body: web::Json<AdminUserClassSchema>,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+
+    require_permission(&data.db, &user, Permission::ManageUsers).await?;
+    fetch_target_user(&data.db, &user, body.user_id).await?;
+
+    let (exists,) = sqlx::query_as::<_, (i64,)>("SELECT COUNT(*) FROM user_classes WHERE name = ?")
+        .bind(&body.class)
+        .fetch_one(&data.db)
+        .await?;
+    if exists < 1 {
+        return Err(api_error(
+            ApiErrorType::InvalidRequest,
+            "Class doesn't exist",
+        ));
+    }
+
+    // otherwise users could hand out permissions they don't have themselves
+    if !has_permission(&data.db, &user, Permission::ManageClasses).await?
+        && has_permissions_beyond(&data.db, &body.class, &user.class).await?
+    {
+        return Err(api_error(
+            ApiErrorType::Forbidden,
+            "Class has permissions your class doesn't",
+        ));
+    }
+
+    sqlx::query("UPDATE users SET class = ? WHERE id = ?")
+        .bind(&body.class)
+        .bind(body.user_id)
+        .execute(&data.db)
+        .await?;
+
+    Ok(api_success(fetch_admin_user(&data.db, body.user_id).await?))
+}
+
+#[post("/user/ban", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn admin_user_ban_handler(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    body: web::Json<AdminUserBanSchema>,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+
+    require_permission(&data.db, &user, Permission::ManageUsers).await?;
+    fetch_target_user(&data.db, &user, body.user_id).await?;
+
+    if body.reason.chars().count() > 255 {
+        return Err(api_error(
+            ApiErrorType::InvalidRequest,
+            "Ban reasons can be at most 255 characters",
+        ));
+    }
+    if body.expires_at.is_some_and(|e| e <= Utc::now()) {
+        return Err(api_error(
+            ApiErrorType::InvalidRequest,
+            "Ban expiration is in the past",
+        ));
+    }
+
+    let mut transaction = data.db.begin().await?;
+    // the new ban replaces any current one, so a permanent ban can be shortened
+    sqlx::query(
+        format!(
+            "UPDATE user_bans SET lifted_at = NOW() WHERE user_id = ? AND {}",
+            ACTIVE_BAN_CONDITION
+        )
+        .as_str(),
+    )
+    .bind(body.user_id)
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query(
+        "INSERT INTO user_bans (user_id, banned_by, reason, expires_at) VALUES (?, ?, ?, ?)",
+    )
+    .bind(body.user_id)
+    .bind(user.id)
+    .bind(body.reason.trim())
+    .bind(body.expires_at)
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+
+    // bans are checked on every request anyway, but this keeps them from coming back logged in
+    revoke_sessions(&data.db, body.user_id, None).await?;
+
+    Ok(api_success(fetch_admin_user(&data.db, body.user_id).await?))
+}
+
+#[post("/user/unban", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn admin_user_unban_handler(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    body: web::Json<AdminUserUnbanSchema>,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+
+    require_permission(&data.db, &user, Permission::ManageUsers).await?;
+    fetch_target_user(&data.db, &user, body.user_id).await?;
+
+    sqlx::query(
+        format!(
+            "UPDATE user_bans SET lifted_at = NOW() WHERE user_id = ? AND {}",
+            ACTIVE_BAN_CONDITION
+        )
+        .as_str(),
+    )
+    .bind(body.user_id)
+    .execute(&data.db)
+    .await?;
+
+    Ok(api_success(fetch_admin_user(&data.db, body.user_id).await?))
+}
+
+#[post("/user/delete", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn admin_user_delete_handler(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    body: web::Json<AdminUserDeleteSchema>,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+
+    require_permission(&data.db, &user, Permission::ManageUsers).await?;
+    fetch_target_user(&data.db, &user, body.user_id).await?;
+
+    let mut transaction = data.db.begin().await?;
+
+    match body.reassign_to {
+        Some(reassign_to) => {
+            if reassign_to == body.user_id {
+                return Err(api_error(
+                    ApiErrorType::InvalidRequest,
+                    "Can't reassign posts to the account being deleted",
+                ));
+            }
+
+            let (exists,) = sqlx::query_as::<_, (i64,)>("SELECT COUNT(*) FROM users WHERE id = ?")
+                .bind(reassign_to)
+                .fetch_one(&mut *transaction)
+                .await?;
+            if exists < 1 {
+                return Err(api_error(
+                    ApiErrorType::InvalidRequest,
+                    "User to reassign posts to doesn't exist",
+                ));
+            }
+
+            sqlx::query("UPDATE images SET owner_id = ? WHERE owner_id = ?")
+                .bind(reassign_to)
+                .bind(body.user_id)
+                .execute(&mut *transaction)
+                .await?;
+            sqlx::query("UPDATE pools SET user_id = ? WHERE user_id = ?")
+                .bind(reassign_to)
+                .bind(body.user_id)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        None if body.mode == AdminUserDeleteMode::Delete => {
+            // posts can't be left without an owner, and pools would be deleted along with the account
+            let (posts,) =
+                sqlx::query_as::<_, (i64,)>("SELECT COUNT(*) FROM images WHERE owner_id = ?")
+                    .bind(body.user_id)
+                    .fetch_one(&mut *transaction)
+                    .await?;
+            let (pools,) =
+                sqlx::query_as::<_, (i64,)>("SELECT COUNT(*) FROM pools WHERE user_id = ?")
+                    .bind(body.user_id)
+                    .fetch_one(&mut *transaction)
+                    .await?;
+            if posts > 0 || pools > 0 {
+                return Err(api_error(
+                    ApiErrorType::InvalidRequest,
+                    "User has posts or pools, give a user to reassign them to or anonymize the account instead",
+                ));
+            }
+        }
+        None => {}
+    }
+
+    // invites that were never used would still let people sign up through the account
+    sqlx::query("DELETE FROM user_invites WHERE creator_id = ? AND redeemed = 0")
+        .bind(body.user_id)
+        .execute(&mut *transaction)
+        .await?;
+
+    match body.mode {
+        AdminUserDeleteMode::Delete => {
+            // everything else the account owns is deleted with it, or has its user cleared
+            sqlx::query("DELETE FROM users WHERE id = ?")
+                .bind(body.user_id)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        AdminUserDeleteMode::Anonymize => {
+            for table in [
+                "user_sessions",
+                "user_api_keys",
+                "password_resets",
+                "user_config",
+            ] {
+                sqlx::query(format!("DELETE FROM {} WHERE user_id = ?", table).as_str())
+                    .bind(body.user_id)
+                    .execute(&mut *transaction)
+                    .await?;
+            }
+
+            // without a password nobody can log in to it again
+            sqlx::query(
+                "UPDATE users SET name = ?, pass = NULL, email = NULL, class = ? WHERE id = ?",
+            )
+            .bind(format!("deleted_{}", body.user_id))
+            .bind(DEFAULT_CLASS)
+            .bind(body.user_id)
+            .execute(&mut *transaction)
+            .await?;
+        }
+    }
+
+    transaction.commit().await?;
+
+    Ok(api_success("success"))
+}
@@ -12,7 +12,7 @@ use crate::{
     error::{api_error, api_success, ApiError, ApiErrorType},
     modules::{
         import::{resolvers::get_resolver, schema::ImportResolveSchema},
-        posts::model::PostModel,
+        posts::{model::PostModel, source::find_posts_by_source},
         users::middleware::AuthFactory,
     },
     AppState,
@@ -60,12 +60,16 @@ pub async fn import_resolve_handler(
 #[post("/prepare", wrap = "AuthFactory { reject_unauthed: true }")]
 pub async fn import_prepare_handler(
     body: web::Json<ImportPrepareSchema>,
+    data: web::Data<AppState>,
 ) -> Result<HttpResponse, ApiError> {
     let service = get_service(body.url.clone()).ok_or(api_error(
         ApiErrorType::InvalidRequest,
         "Unknown or unsupported service",
     ))?;
 
-    let result = service.prepare(body.url.clone()).await?;
+    let mut result = service.prepare(body.url.clone()).await?;
+    // warn about posts that were already imported from this source
+    result.duplicates = find_posts_by_source(&data.db, result.source.as_str()).await?;
+
     Ok(api_success(result))
 }
@@ -4,6 +4,7 @@ use once_cell::sync::Lazy;
 use regex::Regex;
 
 use crate::error::{api_error, ApiError, ApiErrorType};
+use crate::modules::posts::source::normalize_source;
 use crate::util::http::create_client;
 
 use super::super::service::{ImportService, ImportServicePrepareResult};
@@ -34,6 +35,9 @@ impl ImportService for E926ImportService {
         let (post_id, service) =
             decode_url(url).ok_or(api_error(ApiErrorType::InvalidRequest, "Invalid post URL"))?;
         let url = format!("https://{}.net/posts/{}.json", service, post_id);
+        let source =
+            normalize_source(format!("https://{}.net/posts/{}", service, post_id).as_str())
+                .unwrap_or_default();
 
         let client = create_client()?;
         let result = client.get(url).send().await.map_err(|e| {
@@ -80,6 +84,8 @@ impl ImportService for E926ImportService {
             image_url: response.post.file.url,
             tags,
             service: "e926".to_owned(),
+            source,
+            duplicates: Vec::new(),
         });
     }
 }
@@ -8,6 +8,10 @@ pub struct ImportServicePrepareResult {
     pub image_url: String,
     pub tags: Vec<String>,
     pub service: String,
+    /// The normalized URL of the post being imported, to be stored as the new post's source
+    pub source: String,
+    /// Existing posts that already have this source
+    pub duplicates: Vec<i32>,
 }
 
 #[async_trait]
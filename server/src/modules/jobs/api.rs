@@ -0,0 +1,66 @@
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use itertools::Itertools;
+
+use super::model::{JobListResponse, JobModel, JobResponse};
+use super::schema::{JobListSchema, JobStatusSchema};
+use crate::{
+    error::{api_error, api_success, ApiError, ApiErrorType},
+    modules::users::{
+        middleware::{get_user, AuthFactory},
+        permissions::{has_permission, Permission},
+    },
+    AppState,
+};
+
+#[get("/status", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn job_status_handler(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    body: web::Query<JobStatusSchema>,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+
+    let job = sqlx::query_as::<_, JobModel>("SELECT * FROM background_jobs WHERE id = ?")
+        .bind(body.id)
+        .fetch_optional(&data.db)
+        .await?;
+
+    let Some(job) = job else {
+        return Err(api_error(ApiErrorType::InvalidRequest, "Job not found"));
+    };
+
+    if job.user_id != Some(user.id)
+        && !has_permission(&data.db, &user, Permission::ManageSystem).await?
+    {
+        return Err(api_error(ApiErrorType::InvalidRequest, "Job not found"));
+    }
+
+    Ok(api_success(JobResponse::from_model(job)))
+}
+
+#[get("/list", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn job_list_handler(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    body: web::Query<JobListSchema>,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+
+    let limit = body.limit.unwrap_or(20).clamp(1, 100);
+    let offset = body.offset.unwrap_or(0).max(0);
+
+    let jobs = sqlx::query_as::<_, JobModel>(
+        "SELECT * FROM background_jobs WHERE user_id = ? ORDER BY id DESC LIMIT ? OFFSET ?",
+    )
+    .bind(user.id)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(&data.db)
+    .await?;
+
+    Ok(api_success(JobListResponse {
+        jobs: jobs.into_iter().map(JobResponse::from_model).collect_vec(),
+    }))
+}
@@ -0,0 +1,229 @@
+use std::future::Future;
+
+use actix_web::{web, Scope};
+use log::{error, info};
+use model::JobModel;
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::MySqlPool;
+
+use crate::{
+    error::ApiError,
+    modules::system::{
+        media_info::{run_media_info_backfill, MediaInfoBackfillSchema},
+        sources::run_source_normalization,
+        thumbnails::{run_thumbnail_regeneration, ThumbnailRegenerationSchema},
+    },
+    AppState,
+};
+
+mod api;
+pub mod model;
+mod schema;
+
+pub fn scope() -> Scope {
+    web::scope("/job")
+        .service(api::job_status_handler)
+        .service(api::job_list_handler)
+}
+
+/// Lets a running job report how far along it is.
+#[derive(Clone)]
+pub struct JobHandle {
+    pub id: i32,
+    db: MySqlPool,
+    /// Where the job got to before it was interrupted, if it's being resumed
+    pub cursor: Option<i64>,
+    /// The partial result saved with the cursor
+    saved_result: Option<String>,
+}
+
+impl JobHandle {
+    pub async fn set_progress(&self, progress: i32, total: i32) -> Result<(), ApiError> {
+        sqlx::query("UPDATE background_jobs SET progress = ?, total = ? WHERE id = ?")
+            .bind(progress)
+            .bind(total)
+            .bind(self.id)
+            .execute(&self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Records progress along with enough state to resume the job from this point.
+    pub async fn checkpoint(
+        &self,
+        progress: i32,
+        total: i32,
+        cursor: i64,
+        partial_result: &impl Serialize,
+    ) -> Result<(), ApiError> {
+        sqlx::query(
+            "UPDATE background_jobs SET progress = ?, total = ?, `cursor` = ?, result = ? WHERE id = ?",
+        )
+        .bind(progress)
+        .bind(total)
+        .bind(cursor)
+        .bind(serde_json::to_string(partial_result).ok())
+        .bind(self.id)
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// The partial result from the last checkpoint, if the job is being resumed.
+    pub fn saved_result<T: DeserializeOwned>(&self) -> Option<T> {
+        self.saved_result
+            .as_ref()
+            .and_then(|r| serde_json::from_str(r).ok())
+    }
+}
+
+async fn finish_job(
+    db: &MySqlPool,
+    id: i32,
+    state: &str,
+    result: serde_json::Value,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE background_jobs SET state = ?, result = ? WHERE id = ?")
+        .bind(state)
+        .bind(result.to_string())
+        .bind(id)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+fn run_job<F, Fut, T>(handle: JobHandle, kind: String, job: F)
+where
+    F: FnOnce(JobHandle) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<T, ApiError>> + Send + 'static,
+    T: Serialize + Send,
+{
+    tokio::spawn(async move {
+        let db = handle.db.clone();
+        let id = handle.id;
+        info!("Starting {} job {}", kind, id);
+
+        if let Err(e) = sqlx::query("UPDATE background_jobs SET state = 'running' WHERE id = ?")
+            .bind(id)
+            .execute(&db)
+            .await
+        {
+            error!("Failed to start job {}: {:?}", id, e);
+            return;
+        }
+
+        let finished = match job(handle).await {
+            Ok(result) => {
+                let result = serde_json::to_value(result).unwrap_or(serde_json::Value::Null);
+                finish_job(&db, id, "done", result).await
+            }
+            Err(e) => {
+                error!("Job {} failed: {}", id, e.message);
+                finish_job(&db, id, "failed", serde_json::Value::String(e.message)).await
+            }
+        };
+
+        if let Err(e) = finished {
+            error!("Failed to save result of job {}: {:?}", id, e);
+        }
+    });
+}
+
+/// Records a new job and runs it in the background, returning the job's ID straight away.
+/// Whatever the job returns is stored as its result.
+pub async fn start_job<F, Fut, T>(
+    db: &MySqlPool,
+    kind: &str,
+    user_id: i32,
+    data: &impl Serialize,
+    job: F,
+) -> Result<i32, ApiError>
+where
+    F: FnOnce(JobHandle) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<T, ApiError>> + Send + 'static,
+    T: Serialize + Send,
+{
+    let data = serde_json::to_string(data).unwrap_or("{}".to_owned());
+    let id = sqlx::query("INSERT INTO background_jobs (`kind`, `user_id`, `data`) VALUES (?, ?, ?)")
+        .bind(kind)
+        .bind(user_id)
+        .bind(data)
+        .execute(db)
+        .await?
+        .last_insert_id() as i32;
+
+    let handle = JobHandle {
+        id,
+        db: db.clone(),
+        cursor: None,
+        saved_result: None,
+    };
+    run_job(handle, kind.to_owned(), job);
+
+    Ok(id)
+}
+
+/// Picks up jobs that were still running when the server last stopped.
+/// Jobs that can't be resumed are marked as failed.
+pub async fn resume_jobs(state: &AppState) -> Result<(), ApiError> {
+    let jobs = sqlx::query_as::<_, JobModel>(
+        "SELECT * FROM background_jobs WHERE state IN ('pending', 'running') ORDER BY id ASC",
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    for model in jobs {
+        let handle = JobHandle {
+            id: model.id,
+            db: state.db.clone(),
+            cursor: model.cursor,
+            saved_result: model.result.clone(),
+        };
+
+        match model.kind.as_str() {
+            "regenerate_thumbnails" => {
+                let options: ThumbnailRegenerationSchema =
+                    serde_json::from_str(&model.data).unwrap_or_default();
+                let db = state.db.clone();
+                let storage = state.storage.clone();
+                let config = state.booru_config.clone();
+
+                run_job(handle, model.kind, move |job| {
+                    run_thumbnail_regeneration(job, db, storage, config, options)
+                });
+            }
+            "backfill_media_info" => {
+                let options: MediaInfoBackfillSchema =
+                    serde_json::from_str(&model.data).unwrap_or_default();
+                let db = state.db.clone();
+                let storage = state.storage.clone();
+
+                run_job(handle, model.kind, move |job| {
+                    run_media_info_backfill(job, db, storage, options)
+                });
+            }
+            "normalize_sources" => {
+                let db = state.db.clone();
+
+                run_job(handle, model.kind, move |job| {
+                    run_source_normalization(job, db)
+                });
+            }
+            _ => {
+                info!("Can't resume {} job {}", model.kind, model.id);
+                finish_job(
+                    &state.db,
+                    model.id,
+                    "failed",
+                    serde_json::Value::String("Interrupted by a server restart".to_owned()),
+                )
+                .await?;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct JobModel {
+    pub id: i32,
+    pub kind: String,
+    pub user_id: Option<i32>,
+    pub state: String,
+    pub progress: i32,
+    pub total: i32,
+    pub data: String,
+    pub result: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub cursor: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct JobResponse {
+    pub id: i32,
+    pub kind: String,
+    pub user_id: Option<i32>,
+    /// One of pending, running, done or failed
+    pub state: String,
+    pub progress: i32,
+    pub total: i32,
+    pub data: serde_json::Value,
+    /// The job's output once it's done, or the error message if it failed.
+    /// Jobs that can be resumed also save their partial results here while running
+    pub result: Option<serde_json::Value>,
+    pub created_at: i32,
+    pub updated_at: i32,
+}
+
+impl JobResponse {
+    pub fn from_model(model: JobModel) -> JobResponse {
+        JobResponse {
+            id: model.id,
+            kind: model.kind,
+            user_id: model.user_id,
+            state: model.state,
+            progress: model.progress,
+            total: model.total,
+            data: serde_json::from_str(&model.data).unwrap_or(serde_json::Value::Null),
+            result: model.result.and_then(|r| serde_json::from_str(&r).ok()),
+            created_at: model.created_at.timestamp() as i32,
+            updated_at: model.updated_at.timestamp() as i32,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct JobListResponse {
+    pub jobs: Vec<JobResponse>,
+}
+
+/// Returned by endpoints that start a job.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct JobStartedResponse {
+    pub job_id: i32,
+}
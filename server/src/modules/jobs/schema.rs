@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct JobStatusSchema {
+    pub id: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct JobListSchema {
+    pub limit: Option<i32>,
+    pub offset: Option<i32>,
+}
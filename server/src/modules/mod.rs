@@ -1,5 +1,7 @@
+pub mod admin;
 pub mod favorites;
 pub mod import;
+pub mod jobs;
 pub mod pools;
 pub mod posts;
 pub mod system;
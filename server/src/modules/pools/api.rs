@@ -3,6 +3,7 @@ use crate::modules::pools::schema::{PoolEditSchema, PoolListSchema};
 use crate::modules::posts::model::PostModel;
 use crate::modules::posts::query::model::PostQueryResult;
 use crate::modules::users::middleware::get_user;
+use crate::modules::users::permissions::{has_permission, Permission};
 use crate::{
     error::{api_error, ApiErrorType},
     modules::{pools::model::PoolModel, users::middleware::AuthFactory},
@@ -57,7 +58,8 @@ pub async fn pool_info_handler(
                 e => e.into(),
             })?;
 
-        let post = PostQueryResult::from_model_query(post_result, &data.db).await?;
+        let mut post = PostQueryResult::from_model_query(post_result, &data.db).await?;
+        post.sign_media_urls(&*data.storage)?;
         posts.push(post);
     }
 
@@ -142,7 +144,7 @@ pub async fn pool_post_handler(
             e => e.into(),
         })?;
 
-    if user.class != "admin" && user.id != owner_id {
+    if user.id != owner_id && !has_permission(&data.db, &user, Permission::EditAnyPool).await? {
         return Err(api_error(
             ApiErrorType::Forbidden,
             "Can't perform operations on other people's pools",
@@ -294,7 +296,7 @@ pub async fn pool_edit_handler(
             e => e.into(),
         })?;
 
-    if user.class != "admin" && user.id != pool.user_id {
+    if user.id != pool.user_id && !has_permission(&data.db, &user, Permission::EditAnyPool).await? {
         return Err(api_error(
             ApiErrorType::Forbidden,
             "Can't edit other people's pools",
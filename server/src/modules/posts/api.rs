@@ -3,11 +3,14 @@ use std::collections::HashMap;
 use actix_web::{get, post, web, HttpRequest, HttpResponse};
 use chrono::Utc;
 
+use super::relationships::detach_post;
+use super::renditions::fetch_renditions;
 use super::schema::{PostDeleteSchema, PostInfoSchema, PostVoteSchema};
 use crate::error::api_error_owned;
-use crate::modules::posts::query::model::PostQueryResult;
+use crate::modules::posts::model::PostInfoResponse;
 use crate::modules::posts::schema::PostViewSchema;
 use crate::modules::users::middleware::get_user;
+use crate::modules::users::permissions::{require_permission, Permission};
 use crate::{
     error::{api_error, api_success, ApiError, ApiErrorType},
     modules::{
@@ -32,7 +35,8 @@ pub async fn post_info_handler(
             e => e.into(),
         })?;
 
-    let response = PostQueryResult::from_model_query(result, &data.db).await?;
+    let mut response = PostInfoResponse::from_model_query(result, &data.db).await?;
+    response.post.sign_media_urls(&*data.storage)?;
 
     Ok(api_success(response))
 }
@@ -184,13 +188,7 @@ pub async fn post_delete_handler(
     let user =
         get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
 
-    // TODO: implement real permissions checking
-    if user.class != "admin" {
-        return Err(api_error(
-            ApiErrorType::Forbidden,
-            "Only admin can delete posts",
-        ));
-    }
+    require_permission(&data.db, &user, Permission::DeletePost).await?;
 
     let post = sqlx::query_as!(PostModel, "SELECT * FROM images WHERE id = ?", body.post_id)
         .fetch_one(&data.db)
@@ -205,13 +203,34 @@ pub async fn post_delete_handler(
         .await?;
 
     data.storage
-        .delete_file(data.storage.thumb_path(post.hash))
+        .delete_file(data.storage.thumb_path(post.hash.clone()))
         .await?;
 
+    let renditions = fetch_renditions(&data.db, &[post.id])
+        .await?
+        .remove(&post.id)
+        .unwrap_or_default();
+    for r in renditions {
+        let path = data
+            .storage
+            .rendition_path(post.hash.clone(), r.rendition, r.format);
+        // the JPEG thumbnail is the same file as the thumb path deleted above
+        if path != data.storage.thumb_path(post.hash.clone()) {
+            data.storage.delete_file(path).await?;
+        }
+    }
+
+    let mut transaction = data.db.begin().await?;
+
+    // parent_id has no foreign key, so clean up relationships ourselves
+    detach_post(&mut transaction, &post).await?;
+
     sqlx::query!("DELETE FROM images WHERE id = ?", body.post_id)
-        .execute(&data.db)
+        .execute(&mut *transaction)
         .await?;
 
+    transaction.commit().await?;
+
     Ok(api_success("success"))
 }
 
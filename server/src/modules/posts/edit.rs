@@ -1,19 +1,22 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use actix_web::{post, web, HttpRequest, HttpResponse};
 use itertools::Itertools;
-use sqlx::MySqlPool;
+use sqlx::{Acquire, Executor, MySql, MySqlConnection};
 
 use super::query::alias_resolver::TagAliasResolver;
 use super::schema::PostEditSchema;
+use super::source::normalize_source;
 
 use crate::modules::users::middleware::get_user;
+use crate::modules::users::model::UserModel;
+use crate::modules::users::permissions::{has_permission, require_permission, Permission};
 
 use crate::{
-    error::{api_error, api_success, ApiError, ApiErrorType},
+    error::{api_error, api_error_with_data, api_success, ApiError, ApiErrorType},
     modules::{
         posts::{
-            model::{PostModel, PostResponse},
+            model::{PostInfoResponse, PostModel, PostResponse, PostTagConflict},
             util::fetch_tags,
         },
         users::middleware::AuthFactory,
@@ -21,18 +24,119 @@ use crate::{
     AppState,
 };
 
-pub async fn set_post_tags(
-    db: &MySqlPool,
+/// Makes sure the user is allowed to edit the given post.
+pub async fn check_post_editable<'a, E: Executor<'a, Database = MySql>>(
+    db: E,
+    user: &UserModel,
+    post: &PostModel,
+) -> Result<(), ApiError> {
+    if post.locked != 0 && !has_permission(db, user, Permission::LockPost).await? {
+        return Err(api_error(
+            ApiErrorType::Forbidden,
+            "This post is locked and can only be edited by moderators",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Returns the ID of the latest tag edit of a post, or 0 if its tags have never been edited.
+pub async fn fetch_tags_version<'e, E>(db: E, post_id: i32) -> Result<i32, ApiError>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let (version,) = sqlx::query_as::<_, (Option<i32>,)>(
+        "SELECT MAX(id) FROM post_tag_history WHERE image_id = ?",
+    )
+    .bind(post_id)
+    .fetch_one(db)
+    .await?;
+
+    Ok(version.unwrap_or(0))
+}
+
+/// The post's tags, sorted.
+pub async fn fetch_current_tags<'e, E>(db: E, post_id: i32) -> Result<Vec<String>, ApiError>
+where
+    E: Executor<'e, Database = MySql>,
+{
+    let tags = sqlx::query_as::<_, (String,)>(
+        "SELECT t.tag FROM image_tags AS it INNER JOIN tags AS t ON t.id = it.tag_id WHERE it.image_id = ?",
+    )
+    .bind(post_id)
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|(tag,)| tag)
+    .sorted()
+    .collect_vec();
+
+    Ok(tags)
+}
+
+/// Saves what changed in a tag edit to post_tag_history. Edits that change nothing aren't recorded.
+async fn record_tag_history(
+    conn: &mut MySqlConnection,
+    user_id: i32,
+    post_id: i32,
+    previous_tags: &Vec<(String, i32)>,
+    final_tags: &Vec<String>,
+) -> Result<(), ApiError> {
+    let previous: HashSet<&String> = previous_tags.iter().map(|(tag, _)| tag).collect();
+    let current: HashSet<&String> = final_tags.iter().collect();
+
+    let added = current.difference(&previous).sorted().join(" ");
+    let removed = previous.difference(&current).sorted().join(" ");
+
+    if added.is_empty() && removed.is_empty() {
+        return Ok(());
+    }
+
+    sqlx::query(
+        "INSERT INTO post_tag_history (`image_id`, `user_id`, `tags`, `added`, `removed`) VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind(post_id)
+    .bind(user_id)
+    .bind(current.iter().sorted().join(" "))
+    .bind(added)
+    .bind(removed)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Trims tags and resolves their aliases, the way tags are stored.
+fn normalize_tags(resolver: &TagAliasResolver, tags: &[String]) -> Vec<String> {
+    let tags: Vec<String> = tags
+        .iter()
+        .map(|t| t.trim())
+        .filter(|t| t.len() > 0)
+        .map(|t| t.to_owned())
+        .collect();
+
+    resolver.resolve(&tags)
+}
+
+/// Replaces the tags of a post, keeping tag counts and history up to date.
+/// Pass a transaction to have the whole edit applied atomically.
+pub async fn set_post_tags<'a, A>(
+    db: A,
     user_id: i32,
     post_id: String,
     tags: Vec<String>,
-) -> Result<PostResponse, ApiError> {
+) -> Result<PostResponse, ApiError>
+where
+    A: Acquire<'a, Database = MySql>,
+{
+    let mut conn = db.acquire().await?;
+
     let previous_post = sqlx::query_as!(
         PostModel,
         r#"SELECT * FROM images WHERE id = ?"#,
         post_id.clone()
     )
-    .fetch_one(db)
+    .fetch_one(&mut *conn)
     .await
     .map_err(|e| match e {
         sqlx::Error::RowNotFound => api_error(ApiErrorType::InvalidRequest, "Post not found"),
@@ -44,7 +148,7 @@ pub async fn set_post_tags(
 		JOIN tags AS t ON t.id = it.tag_id WHERE it.image_id = ?"#,
         post_id
     )
-    .fetch_all(db)
+    .fetch_all(&mut *conn)
     .await?
     .iter()
     .map(|e| (e.tag.to_owned(), e.count))
@@ -56,16 +160,9 @@ pub async fn set_post_tags(
         tag_counts.insert(tag.clone(), *count);
     });
 
-    let new_tags: Vec<String> = tags
-        .iter()
-        .map(|t| t.trim())
-        .filter(|t| t.len() > 0)
-        .map(|t| t.to_owned())
-        .collect();
-
-    let resolver = TagAliasResolver::new(db).await?;
-    let final_tags = resolver.resolve(&new_tags);
-    let mut final_tag_objs = fetch_tags(db, &final_tags).await?;
+    let resolver = TagAliasResolver::new(&mut *conn).await?;
+    let final_tags = normalize_tags(&resolver, &tags);
+    let mut final_tag_objs = fetch_tags(&mut *conn, &final_tags).await?;
     final_tag_objs.iter().for_each(|t| {
         tag_counts.insert(t.tag.clone(), t.count);
     });
@@ -91,11 +188,14 @@ pub async fn set_post_tags(
             insert_query = insert_query.bind(t);
         }
 
-        insert_query.execute(db).await?;
+        insert_query.execute(&mut *conn).await?;
 
         // obtain the tag objects for the new tags we've inserted
-        let mut missing_tag_objs =
-            fetch_tags(db, &missing_tags.iter().map(|t| (*t).clone()).collect()).await?;
+        let mut missing_tag_objs = fetch_tags(
+            &mut *conn,
+            &missing_tags.iter().map(|t| (*t).clone()).collect(),
+        )
+        .await?;
 
         missing_tag_objs.iter().for_each(|t| {
             tag_counts.insert(t.tag.clone(), t.count);
@@ -106,7 +206,7 @@ pub async fn set_post_tags(
 
     // delete previous tags
     sqlx::query!("DELETE FROM image_tags WHERE image_id = ?", post_id.clone())
-        .execute(db)
+        .execute(&mut *conn)
         .await?;
 
     if final_tags.len() > 0 {
@@ -122,7 +222,7 @@ pub async fn set_post_tags(
             .collect::<String>()
         );
 
-        sqlx::query(query.as_str()).execute(db).await?;
+        sqlx::query(query.as_str()).execute(&mut *conn).await?;
     }
 
     // edit tag counts
@@ -163,7 +263,7 @@ pub async fn set_post_tags(
             tag_update_query = tag_update_query.bind(count);
         }
 
-        tag_update_query.execute(db).await?;
+        tag_update_query.execute(&mut *conn).await?;
 
         let tag_user_freq_query_str = format!(
 			"INSERT INTO tag_user_frequencies(`user_id`, `tag`, `num`) VALUES {} ON DUPLICATE KEY UPDATE num = num + 1", 
@@ -176,9 +276,18 @@ pub async fn set_post_tags(
             tag_freq_query = tag_freq_query.bind(tag);
         }
 
-        tag_freq_query.execute(db).await?;
+        tag_freq_query.execute(&mut *conn).await?;
     }
 
+    record_tag_history(
+        &mut *conn,
+        user_id,
+        previous_post.id,
+        &previous_tags,
+        &final_tags,
+    )
+    .await?;
+
     let response = PostResponse::from_model(previous_post, Some(final_tags));
 
     Ok(response)
@@ -191,10 +300,132 @@ pub async fn post_edit_handler(
     body: web::Json<PostEditSchema>,
 ) -> Result<HttpResponse, ApiError> {
     let user = get_user(&req).ok_or(api_error(ApiErrorType::InvalidRequest, "missing user"))?;
-    let transaction = data.db.begin().await?;
-    let response =
-        set_post_tags(&data.db, user.id, body.post_id.clone(), body.tags.clone()).await?;
+
+    let mut transaction = data.db.begin().await?;
+
+    // lock the post so concurrent edits can't both pass the conflict check below
+    let post = sqlx::query_as!(
+        PostModel,
+        r#"SELECT * FROM images WHERE id = ? FOR UPDATE"#,
+        body.post_id.clone()
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::RowNotFound => api_error(ApiErrorType::InvalidRequest, "Post not found"),
+        _ => e.into(),
+    })?;
+
+    check_post_editable(&data.db, &user, &post).await?;
+
+    if body.locked.is_some() {
+        require_permission(&data.db, &user, Permission::LockPost).await?;
+    }
+
+    let mut update_clauses = Vec::new();
+    let mut update_clause_parameters = Vec::new();
+
+    if let Some(source) = &body.source {
+        match normalize_source(source) {
+            Some(source) => {
+                if source.len() > 255 {
+                    return Err(api_error(
+                        ApiErrorType::InvalidRequest,
+                        "Source can't be longer than 255 characters",
+                    ));
+                }
+
+                update_clauses.push("source = ?");
+                update_clause_parameters.push(source);
+            }
+            None => update_clauses.push("source = NULL"),
+        }
+    }
+
+    if let Some(rating) = &body.rating {
+        update_clauses.push("rating = ?");
+        update_clause_parameters.push(rating.code().to_owned());
+    }
+
+    if let Some(locked) = body.locked {
+        update_clauses.push("locked = ?");
+        update_clause_parameters.push(
+            match locked {
+                true => "1",
+                false => "0",
+            }
+            .to_owned(),
+        );
+    }
+
+    let edits_tags = body.tags.is_some() || !body.add.is_empty() || !body.remove.is_empty();
+    let checks_tags = body.expected_tags.is_some() || body.tags_version.is_some();
+
+    if edits_tags || checks_tags {
+        let current_tags = fetch_current_tags(&mut *transaction, post.id).await?;
+        let tags_version = fetch_tags_version(&mut *transaction, post.id).await?;
+
+        let version_changed = body.tags_version.is_some_and(|v| v != tags_version);
+        let tags_changed = body.expected_tags.as_ref().is_some_and(|expected| {
+            expected.iter().map(|t| t.trim()).collect::<HashSet<&str>>()
+                != current_tags
+                    .iter()
+                    .map(|t| t.as_str())
+                    .collect::<HashSet<&str>>()
+        });
+
+        if version_changed || tags_changed {
+            return Err(api_error_with_data(
+                ApiErrorType::Conflict,
+                "The post's tags were changed by someone else",
+                PostTagConflict {
+                    tags: current_tags,
+                    tags_version,
+                },
+            ));
+        }
+
+        if edits_tags {
+            // normalize everything first, so `remove` matches tags given as an alias or with padding
+            let resolver = TagAliasResolver::new(&mut *transaction).await?;
+            let remove = normalize_tags(&resolver, &body.remove);
+            let add = normalize_tags(&resolver, &body.add);
+
+            let mut tags = match &body.tags {
+                Some(tags) => normalize_tags(&resolver, tags),
+                None => current_tags,
+            };
+            tags.retain(|t| !remove.contains(t));
+            for tag in add {
+                if !tags.contains(&tag) {
+                    tags.push(tag);
+                }
+            }
+
+            set_post_tags(&mut *transaction, user.id, body.post_id.clone(), tags).await?;
+        }
+    }
+
+    if !update_clauses.is_empty() {
+        let query_str = format!(
+            "UPDATE images SET {} WHERE id = ?",
+            update_clauses.join(", ")
+        );
+
+        let mut query_obj = sqlx::query(&query_str);
+        for param in update_clause_parameters {
+            query_obj = query_obj.bind(param);
+        }
+
+        query_obj.bind(post.id).execute(&mut *transaction).await?;
+    }
     transaction.commit().await?;
 
+    let post = sqlx::query_as!(PostModel, r#"SELECT * FROM images WHERE id = ?"#, post.id)
+        .fetch_one(&data.db)
+        .await?;
+    let mut response = PostInfoResponse::from_model_query(post, &data.db).await?;
+    response.post.sign_media_urls(&*data.storage)?;
+
     Ok(api_success(response))
 }
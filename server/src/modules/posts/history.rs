@@ -0,0 +1,136 @@
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
+use itertools::Itertools;
+
+use super::edit::{check_post_editable, fetch_current_tags, fetch_tags_version, set_post_tags};
+use super::schema::{PostHistorySchema, PostRevertSchema, PostUserHistorySchema};
+use crate::{
+    error::{api_error, api_error_with_data, api_success, ApiError, ApiErrorType},
+    modules::{
+        posts::model::{
+            PostInfoResponse, PostModel, PostTagConflict, PostTagHistoryListResponse,
+            PostTagHistoryModel, PostTagHistoryResponse,
+        },
+        users::middleware::{get_user, AuthFactory},
+    },
+    AppState,
+};
+
+static HISTORY_SELECT: &str = "SELECT h.*, u.name AS user_name FROM post_tag_history AS h
+	LEFT JOIN users AS u ON u.id = h.user_id";
+
+#[get("/history", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn post_history_handler(
+    data: web::Data<AppState>,
+    body: web::Query<PostHistorySchema>,
+) -> Result<HttpResponse, ApiError> {
+    let history = sqlx::query_as::<_, PostTagHistoryModel>(
+        format!("{} WHERE h.image_id = ? ORDER BY h.id DESC", HISTORY_SELECT).as_str(),
+    )
+    .bind(body.post_id)
+    .fetch_all(&data.db)
+    .await?;
+
+    Ok(api_success(PostTagHistoryListResponse {
+        history: history
+            .into_iter()
+            .map(PostTagHistoryResponse::from_model)
+            .collect_vec(),
+    }))
+}
+
+#[get("/history/user", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn post_user_history_handler(
+    data: web::Data<AppState>,
+    body: web::Query<PostUserHistorySchema>,
+) -> Result<HttpResponse, ApiError> {
+    let limit = body.limit.unwrap_or(50).clamp(1, 100);
+    let offset = body.offset.unwrap_or(0).max(0);
+
+    let history = sqlx::query_as::<_, PostTagHistoryModel>(
+        format!(
+            "{} WHERE h.user_id = ? ORDER BY h.id DESC LIMIT ? OFFSET ?",
+            HISTORY_SELECT
+        )
+        .as_str(),
+    )
+    .bind(body.user_id)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(&data.db)
+    .await?;
+
+    Ok(api_success(PostTagHistoryListResponse {
+        history: history
+            .into_iter()
+            .map(PostTagHistoryResponse::from_model)
+            .collect_vec(),
+    }))
+}
+
+#[post("/revert", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn post_revert_handler(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    body: web::Json<PostRevertSchema>,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+
+    let mut transaction = data.db.begin().await?;
+
+    // lock the post so a concurrent edit can't slip in between the version check and the revert
+    let post = sqlx::query_as!(
+        PostModel,
+        "SELECT * FROM images WHERE id = ? FOR UPDATE",
+        body.post_id
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::RowNotFound => api_error(ApiErrorType::InvalidRequest, "Post not found"),
+        e => e.into(),
+    })?;
+
+    check_post_editable(&data.db, &user, &post).await?;
+
+    if let Some(expected_version) = body.tags_version {
+        let tags_version = fetch_tags_version(&mut *transaction, post.id).await?;
+        if expected_version != tags_version {
+            return Err(api_error_with_data(
+                ApiErrorType::Conflict,
+                "The post's tags were changed by someone else",
+                PostTagConflict {
+                    tags: fetch_current_tags(&mut *transaction, post.id).await?,
+                    tags_version,
+                },
+            ));
+        }
+    }
+
+    let entry = sqlx::query_as::<_, PostTagHistoryModel>(
+        format!("{} WHERE h.id = ? AND h.image_id = ?", HISTORY_SELECT).as_str(),
+    )
+    .bind(body.history_id)
+    .bind(body.post_id)
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::RowNotFound => {
+            api_error(ApiErrorType::InvalidRequest, "History entry not found")
+        }
+        e => e.into(),
+    })?;
+
+    // the revert is recorded as a new edit, so it can be undone like any other
+    let tags = PostTagHistoryResponse::from_model(entry).tags;
+    set_post_tags(&mut *transaction, user.id, post.id.to_string(), tags).await?;
+    transaction.commit().await?;
+
+    let post = sqlx::query_as!(PostModel, "SELECT * FROM images WHERE id = ?", post.id)
+        .fetch_one(&data.db)
+        .await?;
+    let mut response = PostInfoResponse::from_model_query(post, &data.db).await?;
+    response.post.sign_media_urls(&*data.storage)?;
+
+    Ok(api_success(response))
+}
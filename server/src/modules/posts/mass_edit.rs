@@ -0,0 +1,285 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use itertools::Itertools;
+use sqlx::MySqlPool;
+
+use super::edit::set_post_tags;
+use super::query::alias_resolver::TagAliasResolver;
+use super::query::query_engine::QueryEngine;
+use super::schema::PostMassEditSchema;
+use crate::{
+    error::{api_error, api_success, ApiError, ApiErrorType},
+    modules::{
+        jobs::{model::JobStartedResponse, start_job, JobHandle},
+        posts::model::{PostMassEditDryRunResponse, PostMassEditResult},
+        users::{
+            middleware::{get_user, AuthFactory},
+            permissions::{has_permission, require_permission, Permission},
+        },
+    },
+    AppState,
+};
+
+// how many posts to look up at once
+const CHUNK_SIZE: usize = 100;
+
+/// A post whose tags would be changed by a mass edit.
+struct PlannedEdit {
+    post_id: i32,
+    locked: bool,
+    previous_tags: HashSet<String>,
+    tags: HashSet<String>,
+}
+
+fn apply_tag_delta(tags: &Vec<String>, edit: &PostMassEditSchema) -> Vec<String> {
+    let mut result = tags
+        .iter()
+        .filter(|t| !edit.remove.contains(t))
+        .map(|t| {
+            edit.replace
+                .iter()
+                .find(|(from, _)| from == t)
+                .map(|(_, to)| to.clone())
+                .unwrap_or(t.clone())
+        })
+        .collect_vec();
+
+    for tag in &edit.add {
+        if !result.contains(tag) {
+            result.push(tag.clone());
+        }
+    }
+
+    result
+}
+
+/// Works out the new tags for a set of posts, leaving out posts the edit wouldn't change.
+async fn plan_edits(
+    db: &MySqlPool,
+    resolver: &TagAliasResolver,
+    post_ids: &[i32],
+    edit: &PostMassEditSchema,
+) -> Result<Vec<PlannedEdit>, ApiError> {
+    if post_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let post_ids_str = post_ids.iter().map(|id| id.to_string()).join(",");
+
+    let mut post_tags: HashMap<i32, Vec<String>> = HashMap::new();
+    let tag_query = format!("SELECT it.image_id, t.tag FROM image_tags AS it INNER JOIN tags AS t ON it.tag_id = t.id WHERE it.image_id IN ({})", post_ids_str);
+    for (post_id, tag) in sqlx::query_as::<_, (i32, String)>(tag_query.as_str())
+        .fetch_all(db)
+        .await?
+    {
+        post_tags.entry(post_id).or_default().push(tag);
+    }
+
+    let locked_query = format!(
+        "SELECT id, locked FROM images WHERE id IN ({})",
+        post_ids_str
+    );
+    let locked: HashMap<i32, bool> = sqlx::query_as::<_, (i32, i8)>(locked_query.as_str())
+        .fetch_all(db)
+        .await?
+        .iter()
+        .map(|(id, locked)| (*id, *locked != 0))
+        .collect();
+
+    let mut planned = Vec::new();
+    for post_id in post_ids {
+        // the post was deleted since we searched
+        let Some(locked) = locked.get(post_id) else {
+            continue;
+        };
+
+        let previous = post_tags.remove(post_id).unwrap_or_default();
+        let tags: HashSet<String> = resolver
+            .resolve(&apply_tag_delta(&previous, edit))
+            .into_iter()
+            .collect();
+        let previous_tags: HashSet<String> = previous.into_iter().collect();
+
+        if tags != previous_tags {
+            planned.push(PlannedEdit {
+                post_id: *post_id,
+                locked: *locked,
+                previous_tags,
+                tags,
+            });
+        }
+    }
+
+    Ok(planned)
+}
+
+/// What happened to a single post in a mass edit.
+enum EditOutcome {
+    Edited,
+    Unchanged,
+    Locked,
+    Deleted,
+}
+
+/// Applies a mass edit to one post. Its tags are read again with the post locked, so an edit made
+/// since the search isn't overwritten with tags from before it.
+async fn edit_post(
+    db: &MySqlPool,
+    resolver: &TagAliasResolver,
+    user_id: i32,
+    can_edit_locked: bool,
+    post_id: i32,
+    edit: &PostMassEditSchema,
+) -> Result<EditOutcome, ApiError> {
+    let mut transaction = db.begin().await?;
+
+    let locked = sqlx::query_as::<_, (i8,)>("SELECT locked FROM images WHERE id = ? FOR UPDATE")
+        .bind(post_id)
+        .fetch_optional(&mut *transaction)
+        .await?;
+    let locked = match locked {
+        Some((locked,)) => locked != 0,
+        None => return Ok(EditOutcome::Deleted),
+    };
+    if locked && !can_edit_locked {
+        return Ok(EditOutcome::Locked);
+    }
+
+    let previous = sqlx::query_as::<_, (String,)>(
+        "SELECT t.tag FROM image_tags AS it INNER JOIN tags AS t ON it.tag_id = t.id WHERE it.image_id = ?",
+    )
+    .bind(post_id)
+    .fetch_all(&mut *transaction)
+    .await?
+    .into_iter()
+    .map(|(tag,)| tag)
+    .collect_vec();
+
+    let tags: HashSet<String> = resolver
+        .resolve(&apply_tag_delta(&previous, edit))
+        .into_iter()
+        .collect();
+    if tags == previous.into_iter().collect::<HashSet<String>>() {
+        return Ok(EditOutcome::Unchanged);
+    }
+
+    set_post_tags(
+        &mut *transaction,
+        user_id,
+        post_id.to_string(),
+        tags.into_iter().collect_vec(),
+    )
+    .await?;
+    transaction.commit().await?;
+
+    Ok(EditOutcome::Edited)
+}
+
+async fn run_mass_edit(
+    job: JobHandle,
+    db: MySqlPool,
+    user_id: i32,
+    can_edit_locked: bool,
+    post_ids: Vec<i32>,
+    edit: PostMassEditSchema,
+) -> Result<PostMassEditResult, ApiError> {
+    let resolver = TagAliasResolver::new(&db).await?;
+    let total = post_ids.len() as i32;
+
+    let mut result = PostMassEditResult {
+        matched: total,
+        edited: 0,
+        skipped_locked: 0,
+        skipped_deleted: 0,
+    };
+
+    let mut done = 0;
+    for chunk in post_ids.chunks(CHUNK_SIZE) {
+        for post_id in chunk {
+            match edit_post(&db, &resolver, user_id, can_edit_locked, *post_id, &edit).await? {
+                EditOutcome::Edited => result.edited += 1,
+                EditOutcome::Unchanged => {}
+                EditOutcome::Locked => result.skipped_locked += 1,
+                EditOutcome::Deleted => result.skipped_deleted += 1,
+            }
+        }
+
+        done += chunk.len() as i32;
+        job.set_progress(done, total).await?;
+    }
+
+    Ok(result)
+}
+
+#[post("/mass_edit", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn post_mass_edit_handler(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    body: web::Json<PostMassEditSchema>,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+    require_permission(&data.db, &user, Permission::MassEditPosts).await?;
+
+    // a query with nothing posts have to match, like an empty one or `-foo`, matches nearly every post on the board
+    let image_query = QueryEngine::parse_query(&data.db, &body.query).await?;
+    if !image_query.has_positive_condition() {
+        return Err(api_error(
+            ApiErrorType::InvalidRequest,
+            "The search query needs at least one tag or condition posts have to match",
+        ));
+    }
+
+    if body.add.is_empty() && body.remove.is_empty() && body.replace.is_empty() {
+        return Err(api_error(
+            ApiErrorType::InvalidRequest,
+            "No tag changes given",
+        ));
+    }
+
+    let resolver = TagAliasResolver::new(&data.db).await?;
+    let post_ids = QueryEngine::find_ids(&data.db, &image_query).await?;
+
+    let can_edit_locked = has_permission(&data.db, &user, Permission::LockPost).await?;
+
+    if body.dry_run {
+        let mut response = PostMassEditDryRunResponse {
+            matched: post_ids.len() as i32,
+            post_ids: Vec::new(),
+            locked_post_ids: Vec::new(),
+            added: BTreeMap::new(),
+            removed: BTreeMap::new(),
+        };
+
+        for chunk in post_ids.chunks(CHUNK_SIZE) {
+            for planned in plan_edits(&data.db, &resolver, chunk, &body).await? {
+                if planned.locked && !can_edit_locked {
+                    response.locked_post_ids.push(planned.post_id);
+                    continue;
+                }
+
+                for tag in planned.tags.difference(&planned.previous_tags) {
+                    *response.added.entry(tag.clone()).or_insert(0) += 1;
+                }
+                for tag in planned.previous_tags.difference(&planned.tags) {
+                    *response.removed.entry(tag.clone()).or_insert(0) += 1;
+                }
+
+                response.post_ids.push(planned.post_id);
+            }
+        }
+
+        return Ok(api_success(response));
+    }
+
+    let db = data.db.clone();
+    let user_id = user.id;
+    let edit = body.into_inner();
+    let job_id = start_job(&data.db, "mass_edit", user_id, &edit.clone(), move |job| {
+        run_mass_edit(job, db, user_id, can_edit_locked, post_ids, edit)
+    })
+    .await?;
+
+    Ok(api_success(JobStartedResponse { job_id }))
+}
@@ -2,10 +2,15 @@ use actix_web::{web, Scope};
 
 mod api;
 mod edit;
+mod history;
+mod mass_edit;
 pub mod model;
 pub mod new;
 pub mod query;
+mod relationships;
+pub mod renditions;
 mod schema;
+pub mod source;
 mod util;
 
 pub fn scope() -> Scope {
@@ -18,4 +23,9 @@ pub fn scope() -> Scope {
         .service(query::api::post_list_handler)
         .service(api::post_delete_handler)
         .service(api::post_view_handler)
+        .service(relationships::post_parent_handler)
+        .service(history::post_history_handler)
+        .service(history::post_user_history_handler)
+        .service(history::post_revert_handler)
+        .service(mass_edit::post_mass_edit_handler)
 }
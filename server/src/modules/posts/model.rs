@@ -1,9 +1,16 @@
+use std::collections::BTreeMap;
+
 use chrono::{DateTime, Utc};
 
 use serde::{Deserialize, Serialize};
-use sqlx::{self};
+use sqlx::{self, MySqlPool};
+
+use super::edit::fetch_tags_version;
+use super::query::model::PostQueryResult;
+use super::relationships::fetch_relatives;
+use crate::error::ApiError;
 
-#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Hash, sqlx::FromRow, Clone)]
+#[derive(Debug, Deserialize, Serialize, PartialEq, sqlx::FromRow, Clone)]
 #[allow(non_snake_case)]
 pub struct PostModel {
     pub id: i32,
@@ -30,6 +37,42 @@ pub struct PostModel {
     pub parent_id: Option<i32>,
     pub has_children: i8,
     pub views: i32,
+    pub rating: String,
+    pub frame_rate: Option<f32>,
+    pub bit_depth: Option<u8>,
+    pub original_hash: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PostRating {
+    Safe,
+    Questionable,
+    Explicit,
+    Unknown,
+}
+
+impl PostRating {
+    /// The single character code stored in the rating column.
+    pub fn code(&self) -> &'static str {
+        match self {
+            PostRating::Safe => "s",
+            PostRating::Questionable => "q",
+            PostRating::Explicit => "e",
+            PostRating::Unknown => "?",
+        }
+    }
+
+    /// Parses either a rating code or a full rating name.
+    pub fn parse(value: &str) -> Option<PostRating> {
+        match value.to_lowercase().as_str() {
+            "s" | "safe" => Some(PostRating::Safe),
+            "q" | "questionable" => Some(PostRating::Questionable),
+            "e" | "explicit" => Some(PostRating::Explicit),
+            "?" | "unknown" => Some(PostRating::Unknown),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -48,6 +91,10 @@ pub struct PostResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tags: Option<Vec<String>>,
     pub views: i32,
+    pub parent_id: Option<i32>,
+    pub has_children: bool,
+    pub rating: PostRating,
+    pub locked: bool,
 }
 
 impl PostResponse {
@@ -66,10 +113,101 @@ impl PostResponse {
             numeric_score: model.numeric_score,
             tags: tags,
             views: model.views,
+            parent_id: model.parent_id,
+            has_children: model.has_children != 0,
+            rating: PostRating::parse(&model.rating).unwrap_or(PostRating::Unknown),
+            locked: model.locked != 0,
         }
     }
 }
 
+/// A post along with the other posts it's related to.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PostInfoResponse {
+    #[serde(flatten)]
+    pub post: PostQueryResult,
+    /// Other posts that share this post's parent
+    pub siblings: Vec<i32>,
+    pub children: Vec<i32>,
+    /// Changes every time the post's tags are edited
+    pub tags_version: i32,
+}
+
+impl PostInfoResponse {
+    pub async fn from_model_query(
+        model: PostModel,
+        db: &MySqlPool,
+    ) -> Result<PostInfoResponse, ApiError> {
+        let (siblings, children) = fetch_relatives(db, &model).await?;
+        let tags_version = fetch_tags_version(db, model.id).await?;
+        let post = PostQueryResult::from_model_query(model, db).await?;
+
+        Ok(PostInfoResponse {
+            post,
+            siblings,
+            children,
+            tags_version,
+        })
+    }
+}
+
+/// Sent along with the error when a tag edit was based on outdated tags.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PostTagConflict {
+    pub tags: Vec<String>,
+    pub tags_version: i32,
+}
+
+/// A single change to a post's tags. Tag lists are stored space separated.
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct PostTagHistoryModel {
+    pub id: i32,
+    pub image_id: i32,
+    pub user_id: Option<i32>,
+    pub user_name: Option<String>,
+    pub date: DateTime<Utc>,
+    pub tags: String,
+    pub added: String,
+    pub removed: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PostTagHistoryResponse {
+    pub id: i32,
+    pub post_id: i32,
+    pub user_id: Option<i32>,
+    pub user_name: Option<String>,
+    pub date: i32,
+    /// The post's full tag list after this edit
+    pub tags: Vec<String>,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+fn split_tag_list(tags: &str) -> Vec<String> {
+    tags.split_whitespace().map(|t| t.to_owned()).collect()
+}
+
+impl PostTagHistoryResponse {
+    pub fn from_model(model: PostTagHistoryModel) -> PostTagHistoryResponse {
+        PostTagHistoryResponse {
+            id: model.id,
+            post_id: model.image_id,
+            user_id: model.user_id,
+            user_name: model.user_name,
+            date: model.date.timestamp() as i32,
+            tags: split_tag_list(&model.tags),
+            added: split_tag_list(&model.added),
+            removed: split_tag_list(&model.removed),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PostTagHistoryListResponse {
+    pub history: Vec<PostTagHistoryResponse>,
+}
+
 #[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
 #[allow(non_snake_case)]
 pub struct PostViewModel {
@@ -89,3 +227,25 @@ pub struct PendingPost {
     pub mime: String,
     pub source: Option<String>,
 }
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PostMassEditDryRunResponse {
+    /// Number of posts matching the query
+    pub matched: i32,
+    /// Posts whose tags would change
+    pub post_ids: Vec<i32>,
+    /// Locked posts that would change, but will be skipped
+    pub locked_post_ids: Vec<i32>,
+    /// How many posts each tag would be added to or removed from
+    pub added: BTreeMap<String, i32>,
+    pub removed: BTreeMap<String, i32>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PostMassEditResult {
+    pub matched: i32,
+    pub edited: i32,
+    pub skipped_locked: i32,
+    /// Posts deleted between the search and the edit
+    pub skipped_deleted: i32,
+}
@@ -16,6 +16,7 @@ use super::schema::{PoolResponse, PostNewSchema, PostsNewSchema};
 use crate::error::{api_error_owned, ApiKeyedError};
 use crate::modules::posts::new::schema::{PoolModel, PostNewResponse};
 use crate::modules::posts::new::upload::{upload_and_create_post, OwnerContext};
+use crate::modules::posts::source::normalize_source;
 
 use crate::modules::users::middleware::get_user;
 
@@ -113,8 +114,29 @@ pub async fn post_new_handler(
     let mut next_index = 0;
     let mut post_tags_map: HashMap<String, Vec<String>> = HashMap::new();
     let mut post_pool_index_map: HashMap<String, i32> = HashMap::new();
+    let mut post_source_map: HashMap<String, String> = HashMap::new();
     for post in &body_data.posts {
         post_tags_map.insert(post.filename.clone(), post.tags.clone());
+
+        match post.source.as_ref().and_then(|s| normalize_source(s)) {
+            Some(source) if source.len() > 255 => {
+                return Err(api_error(
+                    ApiErrorType::InvalidRequest,
+                    "Source can't be longer than 255 characters",
+                )
+                .into());
+            }
+            Some(source) => {
+                post_source_map.insert(post.filename.clone(), source);
+            }
+            None if post.source.is_none() && post.upload_type == "url" => {
+                // a URL too long to store just leaves the source empty, it wasn't the user's choice
+                if let Some(source) = normalize_source(&post.file).filter(|s| s.len() <= 255) {
+                    post_source_map.insert(post.filename.clone(), source);
+                }
+            }
+            None => {}
+        }
         if let Some(index) = post.pool_index {
             if index < 0 {
                 return Err(
@@ -179,6 +201,7 @@ pub async fn post_new_handler(
         let tags = post_tags_map
             .remove(&filename.clone())
             .unwrap_or(Vec::new());
+        let source = post_source_map.remove(filename);
         let owner = OwnerContext {
             owner_id: user.id,
             owner_ip: ip.clone(),
@@ -187,6 +210,7 @@ pub async fn post_new_handler(
         let (new_filename, post) = upload_and_create_post(
             &data.db,
             tags,
+            source,
             owner,
             &data.storage,
             data.booru_config.clone(),
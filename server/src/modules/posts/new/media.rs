@@ -7,12 +7,15 @@ use std::io::Read;
 use tempfile::NamedTempFile;
 
 use crate::booru_config::{BooruConfig, ThumbnailFit};
+use crate::modules::posts::renditions::StoryboardLayout;
+use crate::storage::{Rendition, RenditionFormat};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum UploadFileType {
     Unsupported,
     Image,
     Video,
+    Audio,
     Flash,
 }
 
@@ -25,6 +28,23 @@ pub struct UploadInfo {
     pub height: u32,
     pub filesize: u64,
     pub hash: String,
+    #[serde(default)]
+    pub has_audio: bool,
+    /// Only known for images
+    #[serde(default)]
+    pub lossless: Option<bool>,
+    /// Codec of the first video stream, for videos
+    #[serde(default)]
+    pub video_codec: Option<String>,
+    /// Average frames per second, for videos
+    #[serde(default)]
+    pub frame_rate: Option<f32>,
+    /// Bits per color channel
+    #[serde(default)]
+    pub bit_depth: Option<u8>,
+    /// Hash of the file as it was uploaded, if metadata was stripped from it
+    #[serde(default)]
+    pub original_hash: Option<String>,
 }
 
 impl UploadInfo {
@@ -36,14 +56,34 @@ impl UploadInfo {
         self.mime.starts_with("image")
     }
 
+    pub fn is_audio(&self) -> bool {
+        self.mime.starts_with("audio")
+    }
+
+    /// Images that play like a video, which only get a thumbnail.
+    pub fn is_animated(&self) -> bool {
+        self.mime == "image/gif" || self.mime == "image/apng"
+    }
+
     pub fn get_ext(&self) -> String {
         match self.mime.as_str() {
             "video/mp4" => "mp4",
             "video/webm" => "webm",
+            "video/x-matroska" => "mkv",
+            "video/quicktime" => "mov",
+            "video/ogg" => "ogv",
+            "audio/mpeg" => "mp3",
+            "audio/ogg" => "ogg",
+            "audio/mp4" => "m4a",
+            "audio/webm" => "weba",
+            "audio/x-matroska" => "mka",
             "image/png" => "png",
+            "image/apng" => "png",
             "image/webp" => "webp",
             "image/jpeg" => "jpeg",
             "image/gif" => "gif",
+            "image/avif" => "avif",
+            "image/jxl" => "jxl",
             "application/x-shockwave-flash" => "swf",
             _ => "unk",
         }
@@ -64,6 +104,52 @@ struct FfprobeStreamInfo {
     height: Option<u32>,
     codec_type: String,
     codec_name: String,
+    avg_frame_rate: Option<String>,
+    r_frame_rate: Option<String>,
+    pix_fmt: Option<String>,
+    bits_per_raw_sample: Option<String>,
+}
+
+impl FfprobeStreamInfo {
+    fn frame_rate(&self) -> Option<f32> {
+        // ffprobe gives rates as fractions like 30000/1001, and 0/0 when it doesn't know
+        let parse = |rate: &String| {
+            let (num, den) = rate.split_once('/')?;
+            let (num, den) = (num.parse::<f32>().ok()?, den.parse::<f32>().ok()?);
+            (num > 0.0 && den > 0.0).then(|| num / den)
+        };
+
+        self.avg_frame_rate
+            .as_ref()
+            .and_then(parse)
+            .or_else(|| self.r_frame_rate.as_ref().and_then(parse))
+    }
+
+    fn bit_depth(&self) -> Option<u8> {
+        if let Some(bits) = self
+            .bits_per_raw_sample
+            .as_ref()
+            .and_then(|b| b.parse::<u8>().ok())
+        {
+            return Some(bits);
+        }
+
+        // otherwise work it out from the pixel format name, like yuv420p10le or rgb48be
+        let pix_fmt = self.pix_fmt.as_ref()?;
+        let depth = [
+            ("p16", 16),
+            ("48", 16),
+            ("64", 16),
+            ("p12", 12),
+            ("p10", 10),
+        ]
+        .iter()
+        .find(|(pattern, _)| pix_fmt.contains(pattern))
+        .map(|(_, depth)| *depth)
+        .unwrap_or(8);
+
+        Some(depth)
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -72,7 +158,7 @@ struct FfprobeResult {
     streams: Vec<FfprobeStreamInfo>,
 }
 
-fn hash_file(file: &NamedTempFile) -> Result<String, String> {
+fn read_file(file: &NamedTempFile) -> Result<Vec<u8>, String> {
     let mut handle = file.reopen().map_err(|e| {
         error!("Error getting temp file handle: {:?}", e);
         "Temp file error".to_owned()
@@ -85,11 +171,120 @@ fn hash_file(file: &NamedTempFile) -> Result<String, String> {
         "Temp file error".to_owned()
     })?;
 
-    Ok(format!("{:x}", md5::compute(bytes)))
+    Ok(bytes)
+}
+
+/// Checks whether a PNG has an animation control chunk, which makes it an APNG.
+fn is_apng(bytes: &[u8]) -> bool {
+    // chunks start after the 8 byte signature, acTL has to come before the image data
+    let mut pos = 8;
+    while pos + 8 <= bytes.len() {
+        let length =
+            u32::from_be_bytes([bytes[pos], bytes[pos + 1], bytes[pos + 2], bytes[pos + 3]]);
+        match &bytes[pos + 4..pos + 8] {
+            b"acTL" => return true,
+            b"IDAT" => return false,
+            _ => pos += 12 + length as usize,
+        }
+    }
+
+    false
+}
+
+/// Whether a file is ISO base media, which starts with an ftyp box.
+fn is_iso_media(bytes: &[u8]) -> bool {
+    bytes.len() >= 12 && &bytes[4..8] == b"ftyp"
+}
+
+/// Works out the format of an ISO base media file from the brands in its ftyp box.
+/// The same container is used for HEIF images and more, so unknown brands aren't assumed to be MP4.
+fn sniff_iso_media(bytes: &[u8]) -> Option<(UploadFileType, &'static str)> {
+    let major = &bytes[8..12];
+    // the compatible brands fill the rest of the box, after the minor version
+    let box_size = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+    let compatible = bytes[..usize::min(box_size, bytes.len())]
+        .get(16..)
+        .unwrap_or(&[])
+        .chunks_exact(4);
+    let mut brands = std::iter::once(major).chain(compatible);
+
+    // AVIF files often have the generic HEIF brands as their major brand
+    if brands.any(|b| b == b"avif" || b == b"avis") {
+        return Some((UploadFileType::Image, "image/avif"));
+    }
+
+    match major {
+        b"qt  " => Some((UploadFileType::Video, "video/quicktime")),
+        b"M4A " | b"M4B " => Some((UploadFileType::Audio, "audio/mp4")),
+        b"isom" | b"iso2" | b"iso3" | b"iso4" | b"iso5" | b"iso6" | b"mp41" | b"mp42" | b"avc1"
+        | b"M4V " | b"M4VP" | b"dash" | b"mmp4" | b"MSNV" | b"XAVC" | b"f4v " | b"3gp4"
+        | b"3gp5" | b"3gp6" | b"3g2a" => Some((UploadFileType::Video, "video/mp4")),
+        // HEIC (heic, heix, mif1, msf1 and friends) and anything else we can't handle
+        _ => None,
+    }
+}
+
+/// Works out the format of a file from its magic bytes, for formats ffprobe can't tell apart.
+/// Containers that can hold either audio or video are reported as video.
+fn sniff_format(bytes: &[u8]) -> Option<(UploadFileType, &'static str)> {
+    const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+    const JXL_CONTAINER: &[u8] = b"\x00\x00\x00\x0cJXL \r\n\x87\n";
+    const EBML_MAGIC: &[u8] = b"\x1a\x45\xdf\xa3";
+
+    if bytes.starts_with(PNG_SIGNATURE) {
+        return Some(match is_apng(bytes) {
+            true => (UploadFileType::Image, "image/apng"),
+            false => (UploadFileType::Image, "image/png"),
+        });
+    }
+
+    if bytes.starts_with(JXL_CONTAINER) || bytes.starts_with(b"\xff\x0a") {
+        return Some((UploadFileType::Image, "image/jxl"));
+    }
+
+    if is_iso_media(bytes) {
+        return sniff_iso_media(bytes);
+    }
+
+    // Matroska and WebM share a header, only the doctype near the start tells them apart
+    if bytes.starts_with(EBML_MAGIC) {
+        let header = &bytes[..usize::min(bytes.len(), 64)];
+        let is_webm = header.windows(4).any(|w| w == b"webm");
+        return Some(match is_webm {
+            true => (UploadFileType::Video, "video/webm"),
+            false => (UploadFileType::Video, "video/x-matroska"),
+        });
+    }
+
+    if bytes.starts_with(b"OggS") {
+        return Some((UploadFileType::Video, "video/ogg"));
+    }
+
+    // either an ID3 tag or a bare MPEG audio frame. ADTS AAC has the same frame sync,
+    // but its layer bits are always zero, which isn't a valid layer for MPEG audio
+    if bytes.starts_with(b"ID3")
+        || (bytes.len() >= 2 && bytes[0] == 0xff && bytes[1] & 0xe0 == 0xe0 && bytes[1] & 0x06 != 0)
+    {
+        return Some((UploadFileType::Audio, "audio/mpeg"));
+    }
+
+    None
+}
+
+/// Whether an image is stored without any compression loss, if the format can tell us.
+fn is_lossless(mime: &str, bytes: &[u8]) -> Option<bool> {
+    match mime {
+        "image/png" | "image/apng" | "image/gif" => Some(true),
+        "image/jpeg" => Some(false),
+        // lossless WebP uses a VP8L chunk instead of VP8
+        "image/webp" => Some(bytes.len() >= 16 && &bytes[12..16] == b"VP8L"),
+        _ => None,
+    }
 }
 
 pub async fn get_content_info(content: &NamedTempFile) -> Result<UploadInfo, String> {
-    let hash = hash_file(content)?;
+    let bytes = read_file(content)?;
+    let hash = format!("{:x}", md5::compute(&bytes));
 
     let filesize = content
         .as_file()
@@ -130,19 +325,31 @@ pub async fn get_content_info(content: &NamedTempFile) -> Result<UploadInfo, Str
         error!("Failed to deserialize ffprobe response: {:?}", e);
         "Can't get file information"
     })?;
-    let (file_type, mime) = match info.format.format_long_name.as_str() {
-        "SWF (ShockWave Flash)" => Ok((UploadFileType::Flash, "application/x-shockwave-flash")),
-        "QuickTime / MOV" => Ok((UploadFileType::Video, "video/mp4")),
-        "Matroska / WebM" => Ok((UploadFileType::Video, "video/webm")),
-        "image2 sequence" => Ok((UploadFileType::Image, "image/jpeg")),
-        "piped jpeg sequence" => Ok((UploadFileType::Image, "image/jpeg")),
-        "piped png sequence" => Ok((UploadFileType::Image, "image/png")),
-        "CompuServe Graphics Interchange Format (GIF)" => Ok((UploadFileType::Image, "image/gif")),
-        "piped webp sequence" => Ok((UploadFileType::Image, "image/webp")),
-        _ => Err(format!(
-            "Unknown file format type {}, {}",
-            info.format.format_long_name, info.format.format_name
-        )),
+
+    let sniffed = sniff_format(&bytes);
+    let (file_type, mime) = match sniffed {
+        Some(format) => Ok(format),
+        // ffprobe calls every ISO media file QuickTime, even the ones we can't handle like HEIC
+        None if is_iso_media(&bytes) => Err("Unsupported file format".to_owned()),
+        None => match info.format.format_long_name.as_str() {
+            "SWF (ShockWave Flash)" => Ok((UploadFileType::Flash, "application/x-shockwave-flash")),
+            "QuickTime / MOV" => Ok((UploadFileType::Video, "video/mp4")),
+            "Matroska / WebM" => Ok((UploadFileType::Video, "video/webm")),
+            "image2 sequence" => Ok((UploadFileType::Image, "image/jpeg")),
+            "piped jpeg sequence" => Ok((UploadFileType::Image, "image/jpeg")),
+            "piped png sequence" => Ok((UploadFileType::Image, "image/png")),
+            "CompuServe Graphics Interchange Format (GIF)" => {
+                Ok((UploadFileType::Image, "image/gif"))
+            }
+            "piped webp sequence" => Ok((UploadFileType::Image, "image/webp")),
+            "piped jpegxl sequence" => Ok((UploadFileType::Image, "image/jxl")),
+            "MP2/3 (MPEG audio layer 2/3)" => Ok((UploadFileType::Audio, "audio/mpeg")),
+            "Ogg" => Ok((UploadFileType::Video, "video/ogg")),
+            _ => Err(format!(
+                "Unknown file format type {}, {}",
+                info.format.format_long_name, info.format.format_name
+            )),
+        },
     }?;
 
     let length = info
@@ -152,90 +359,221 @@ pub async fn get_content_info(content: &NamedTempFile) -> Result<UploadInfo, Str
         .parse::<f64>()
         .map_err(|_e| "Invalid content duration")? as u32;
 
+    // cover art in audio files shows up as a single frame mjpeg or png stream
     let stream = info
         .streams
         .iter()
-        .filter(|s| s.codec_type == "video")
+        .filter(|s| s.codec_type == "video" && s.codec_name != "mjpeg" && s.codec_name != "png")
         .next();
+    let has_audio = info.streams.iter().any(|s| s.codec_type == "audio");
+
+    // containers without any video are audio posts
+    let (file_type, mime) = match (file_type, stream) {
+        (UploadFileType::Video, None) if has_audio => {
+            let mime = match mime {
+                // there's no audio/quicktime, audio-only QuickTime files are what m4a files are
+                "video/quicktime" => "audio/mp4".to_owned(),
+                _ => mime.replacen("video/", "audio/", 1),
+            };
+            (UploadFileType::Audio, mime)
+        }
+        (file_type, _) => (file_type, mime.to_owned()),
+    };
+
+    let is_video = matches!(file_type, UploadFileType::Video);
+    let stream = match file_type {
+        UploadFileType::Audio => None,
+        // images are a single video stream, which may well be an mjpeg or png one
+        UploadFileType::Image => info
+            .streams
+            .iter()
+            .filter(|s| s.codec_type == "video")
+            .next(),
+        _ => stream,
+    };
 
     let width = stream.and_then(|s| s.width).unwrap_or(0);
     let height = stream.and_then(|s| s.height).unwrap_or(0);
 
     Ok(UploadInfo {
         file_type,
-        mime: mime.to_owned(),
+        lossless: is_lossless(&mime, &bytes),
+        mime,
         length: Some(length),
         width,
         height,
         filesize,
         hash,
+        has_audio,
+        video_codec: stream.filter(|_| is_video).map(|s| s.codec_name.clone()),
+        frame_rate: stream.filter(|_| is_video).and_then(|s| s.frame_rate()),
+        bit_depth: stream.and_then(|s| s.bit_depth()),
+        original_hash: None,
     })
 }
 
 fn get_thumbnail_size(config: &BooruConfig, size: (u32, u32)) -> (u32, u32) {
-    match config.thumb_fit {
-        ThumbnailFit::Fit => {
-            let (mut width, mut height) = size;
+    let (mut width, mut height) = size;
 
-            if width < 1 {
-                width = config.thumb_width;
-            }
+    if width < 1 {
+        width = config.thumb_width;
+    }
 
-            if height < 1 {
-                height = config.thumb_height;
-            }
+    if height < 1 {
+        height = config.thumb_height;
+    }
 
-            if width > height * 5 {
-                width = height * 5;
-            } else if height > width * 5 {
-                height = width * 5;
-            }
+    if width > height * 5 {
+        width = height * 5;
+    } else if height > width * 5 {
+        height = width * 5;
+    }
+
+    let x_scale = config.thumb_width as f32 / width as f32;
+    let y_scale = config.thumb_height as f32 / height as f32;
+
+    let scale = f32::max(y_scale, x_scale);
+    (
+        (width as f32 * scale) as u32,
+        (height as f32 * scale) as u32,
+    )
+}
+
+/// Scales to cover the whole thumbnail box, cropping off whatever sticks out.
+fn fill_filter(width: u32, height: u32) -> String {
+    format!(
+        "scale={w}:{h}:force_original_aspect_ratio=increase,crop={w}:{h}",
+        w = width,
+        h = height
+    )
+}
 
-            let x_scale = config.thumb_width as f32 / width as f32;
-            let y_scale = config.thumb_height as f32 / height as f32;
+/// Scales to fit inside the thumbnail box, letterboxed over a blurred copy of the image.
+fn fit_blur_filter(width: u32, height: u32) -> String {
+    format!(
+        "split[bg_in][fg_in];\
+        [bg_in]{fill},boxblur=luma_radius=min(h\\,w)/10:luma_power=2[bg];\
+        [fg_in]scale={w}:{h}:force_original_aspect_ratio=decrease[fg];\
+        [bg][fg]overlay=(W-w)/2:(H-h)/2",
+        fill = fill_filter(width, height),
+        w = width,
+        h = height
+    )
+}
+
+/// Builds the ffmpeg filter graph that turns the content into a thumbnail.
+fn get_thumbnail_filter(config: &BooruConfig, size: (u32, u32)) -> String {
+    let (thumb_width, thumb_height) = (config.thumb_width, config.thumb_height);
 
-            let scale = f32::max(y_scale, x_scale);
-            (
-                (width as f32 * scale) as u32,
-                (height as f32 * scale) as u32,
-            )
+    let filter = match config.thumb_fit {
+        ThumbnailFit::Fit => {
+            let (width, height) = get_thumbnail_size(config, size);
+            format!("scale={}:{}", width, height)
         }
+        ThumbnailFit::FitBlur => fit_blur_filter(thumb_width, thumb_height),
+        ThumbnailFit::FitBlurPortrait => {
+            // only tall content gets letterboxed, wide content is cropped to fill
+            if size.1 > size.0 {
+                fit_blur_filter(thumb_width, thumb_height)
+            } else {
+                fill_filter(thumb_width, thumb_height)
+            }
+        }
+        ThumbnailFit::Fill => fill_filter(thumb_width, thumb_height),
+        ThumbnailFit::Stretch => format!("scale={}:{}", thumb_width, thumb_height),
+    };
+
+    // pick the most representative frame for videos and animations
+    format!("{},thumbnail", filter)
+}
+
+// longest side of video preview clips
+const CLIP_SIZE: u32 = 480;
+// longest side of each storyboard frame
+const STORYBOARD_FRAME_SIZE: u32 = 160;
+const STORYBOARD_COLUMNS: u32 = 10;
+const STORYBOARD_MAX_FRAMES: u32 = 100;
+
+/// The size of the thumbnail [get_thumbnail_filter] produces.
+fn get_thumbnail_dimensions(config: &BooruConfig, size: (u32, u32)) -> (u32, u32) {
+    match config.thumb_fit {
+        ThumbnailFit::Fit => get_thumbnail_size(config, size),
         _ => (config.thumb_width, config.thumb_height),
     }
 }
 
-pub async fn create_thumbnail(
-    config: &BooruConfig,
+/// Scales a size down so its longest side is `max_side`, keeping the aspect ratio.
+fn fit_within(size: (u32, u32), max_side: u32) -> (u32, u32) {
+    let (width, height) = size;
+    let scale = max_side as f32 / u32::max(width, height) as f32;
+
+    (
+        u32::max((width as f32 * scale).round() as u32, 1),
+        u32::max((height as f32 * scale).round() as u32, 1),
+    )
+}
+
+fn encoder_args(format: RenditionFormat) -> &'static [&'static str] {
+    match format {
+        RenditionFormat::Jpeg => &["-qscale:v", "4", "-c:v", "mjpeg", "-f", "image2"],
+        RenditionFormat::Webp => &["-quality", "80", "-c:v", "libwebp", "-f", "webp"],
+        RenditionFormat::Avif => &[
+            "-crf",
+            "32",
+            "-still-picture",
+            "1",
+            "-pix_fmt",
+            "yuv420p",
+            "-c:v",
+            "libaom-av1",
+            "-f",
+            "avif",
+        ],
+        // muted, and with the index up front so clips can start playing before they've fully loaded
+        RenditionFormat::Mp4 => &[
+            "-an",
+            "-c:v",
+            "libx264",
+            "-preset",
+            "veryfast",
+            "-crf",
+            "28",
+            "-pix_fmt",
+            "yuv420p",
+            "-movflags",
+            "+faststart",
+            "-f",
+            "mp4",
+        ],
+    }
+}
+
+/// Runs the content through ffmpeg filters, writing the output to a temp file.
+async fn run_ffmpeg(
     content: &NamedTempFile,
-    info: &UploadInfo,
+    input_args: &[&str],
+    filter_args: &[&str],
+    output_args: &[&str],
 ) -> Result<NamedTempFile, String> {
-    let (width, height) = get_thumbnail_size(config, (info.width, info.height));
-
-    let scale_str = format!("scale={}:{},thumbnail", width, height);
     let out_temp = NamedTempFile::new().map_err(|e| {
-        error!("Error creating thumbnail temp file: {:?}", e);
+        error!("Error creating rendition temp file: {:?}", e);
         "Can't create thumbnail"
     })?;
 
+    let mut args = input_args.to_vec();
+    args.extend([
+        "-i",
+        content.path().to_str().ok_or("Temp file error")?,
+        "-y",
+    ]);
+    args.extend(filter_args);
+    args.extend(output_args);
+    args.push(out_temp.path().to_str().ok_or("Temp file error")?);
+
     let child = Command::new("ffmpeg")
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
-        .args([
-            "-i",
-            content.path().to_str().ok_or("Temp file error")?,
-            "-y",
-            "-vf",
-            scale_str.as_str(),
-            "-f",
-            "image2",
-            "-vframes",
-            "1",
-            "-qscale:v",
-            "4",
-            "-c:v",
-            "mjpeg",
-            out_temp.path().to_str().ok_or("Temp file error")?,
-        ])
+        .args(args)
         .spawn()
         .map_err(|e| {
             error!("error launching ffmpeg: {:?}", e);
@@ -257,3 +595,400 @@ pub async fn create_thumbnail(
 
     Ok(out_temp)
 }
+
+/// Runs a single frame of the content through an ffmpeg filter and encodes it.
+async fn encode_frame(
+    content: &NamedTempFile,
+    filter: &str,
+    format: RenditionFormat,
+) -> Result<NamedTempFile, String> {
+    let mut output_args = vec!["-vframes", "1"];
+    output_args.extend(encoder_args(format));
+
+    run_ffmpeg(content, &[], &["-vf", filter], &output_args).await
+}
+
+/// Draws the waveform of an audio file as an image.
+async fn encode_waveform(
+    content: &NamedTempFile,
+    size: (u32, u32),
+    format: RenditionFormat,
+) -> Result<NamedTempFile, String> {
+    let filter = format!(
+        "[0:a]aformat=channel_layouts=mono,showwavespic=s={}x{}:colors=#8fb3d9",
+        size.0, size.1
+    );
+    let mut output_args = vec!["-vframes", "1"];
+    output_args.extend(encoder_args(format));
+
+    run_ffmpeg(content, &[], &["-filter_complex", &filter], &output_args).await
+}
+
+/// Encodes a short, muted MP4 from the middle of a video.
+async fn encode_clip(
+    content: &NamedTempFile,
+    length: u32,
+    clip_length: u32,
+    size: (u32, u32),
+) -> Result<NamedTempFile, String> {
+    let start = length.saturating_sub(clip_length) / 2;
+    let start_str = start.to_string();
+    let length_str = clip_length.to_string();
+    let filter = format!("scale={}:{}", size.0, size.1);
+
+    run_ffmpeg(
+        content,
+        // seeking before the input is much faster than decoding up to the start
+        &["-ss", start_str.as_str(), "-t", length_str.as_str()],
+        &["-vf", &filter],
+        encoder_args(RenditionFormat::Mp4),
+    )
+    .await
+}
+
+/// Rounds a size down to even numbers, which H.264 needs.
+fn even_size(size: (u32, u32)) -> (u32, u32) {
+    (u32::max(size.0 & !1, 2), u32::max(size.1 & !1, 2))
+}
+
+/// An encoded rendition waiting to be uploaded.
+pub struct RenditionFile {
+    pub rendition: Rendition,
+    pub format: RenditionFormat,
+    /// Size of the rendition, or of a single frame for storyboards
+    pub width: u32,
+    pub height: u32,
+    pub storyboard: Option<StoryboardLayout>,
+    pub file: NamedTempFile,
+}
+
+/// Creates every rendition that applies to the content, each in the configured format and as a JPEG.
+/// Videos also get a muted MP4 clip and a storyboard for scrubbing, audio only gets a waveform thumbnail.
+pub async fn create_renditions(
+    config: &BooruConfig,
+    content: &NamedTempFile,
+    info: &UploadInfo,
+) -> Result<Vec<RenditionFile>, String> {
+    // audio has no picture, so its thumbnail is a drawing of the waveform instead
+    if info.is_audio() {
+        let size = (config.thumb_width, config.thumb_height);
+        let mut renditions = Vec::new();
+        for format in [config.rendition_format, RenditionFormat::Jpeg] {
+            renditions.push(RenditionFile {
+                rendition: Rendition::Thumb,
+                format,
+                width: size.0,
+                height: size.1,
+                storyboard: None,
+                file: encode_waveform(content, size, format).await?,
+            });
+        }
+
+        return Ok(renditions);
+    }
+
+    let size = (info.width, info.height);
+    let mut targets = vec![(
+        Rendition::Thumb,
+        get_thumbnail_filter(config, size),
+        get_thumbnail_dimensions(config, size),
+        None,
+    )];
+
+    // animations and videos are only shown as themselves, so they just get a thumbnail
+    if info.is_image() && !info.is_animated() {
+        let longest_side = u32::max(info.width, info.height);
+
+        for (rendition, max_side) in [
+            (Rendition::Preview, config.preview_size),
+            (Rendition::Sample, config.sample_size),
+        ] {
+            if max_side > 0 && longest_side > max_side {
+                let (width, height) = fit_within(size, max_side);
+                targets.push((
+                    rendition,
+                    format!("scale={}:{}", width, height),
+                    (width, height),
+                    None,
+                ));
+            }
+        }
+    }
+
+    let length = info.length.unwrap_or(0);
+    let is_playable_video = info.is_video() && info.width > 0 && info.height > 0;
+
+    if is_playable_video && length > 0 {
+        // one frame a second for short videos, spread out over longer ones
+        let frames = u32::min(length, STORYBOARD_MAX_FRAMES);
+        let rows = frames.div_ceil(STORYBOARD_COLUMNS);
+        let (width, height) = fit_within(size, STORYBOARD_FRAME_SIZE);
+
+        targets.push((
+            Rendition::Storyboard,
+            format!(
+                "fps={}/{},scale={}:{},tile={}x{}",
+                frames, length, width, height, STORYBOARD_COLUMNS, rows
+            ),
+            (width, height),
+            Some(StoryboardLayout {
+                frames,
+                columns: STORYBOARD_COLUMNS,
+                interval: length * 1000 / frames,
+            }),
+        ));
+    }
+
+    let mut renditions = Vec::new();
+    for (rendition, filter, (width, height), storyboard) in targets {
+        for format in [config.rendition_format, RenditionFormat::Jpeg] {
+            renditions.push(RenditionFile {
+                rendition,
+                format,
+                width,
+                height,
+                storyboard,
+                file: encode_frame(content, &filter, format).await?,
+            });
+        }
+    }
+
+    if is_playable_video && config.clip_length > 0 {
+        let (width, height) = even_size(fit_within(size, CLIP_SIZE));
+        renditions.push(RenditionFile {
+            rendition: Rendition::Clip,
+            format: RenditionFormat::Mp4,
+            width,
+            height,
+            storyboard: None,
+            file: encode_clip(content, length, config.clip_length, (width, height)).await?,
+        });
+    }
+
+    Ok(renditions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LANDSCAPE: (u32, u32) = (768, 384);
+    const PORTRAIT: (u32, u32) = (384, 768);
+
+    const FILL: &str = "scale=192:192:force_original_aspect_ratio=increase,crop=192:192,thumbnail";
+    const FIT_BLUR: &str = "split[bg_in][fg_in];\
+        [bg_in]scale=192:192:force_original_aspect_ratio=increase,crop=192:192,boxblur=luma_radius=min(h\\,w)/10:luma_power=2[bg];\
+        [fg_in]scale=192:192:force_original_aspect_ratio=decrease[fg];\
+        [bg][fg]overlay=(W-w)/2:(H-h)/2,thumbnail";
+
+    fn config(thumb_fit: ThumbnailFit) -> BooruConfig {
+        BooruConfig {
+            upload_count: 3,
+            upload_size: 5 * 1000 * 1000,
+            thumb_width: 192,
+            thumb_height: 192,
+            thumb_fit,
+            rendition_format: RenditionFormat::Webp,
+            preview_size: 1280,
+            sample_size: 2560,
+            clip_length: 3,
+            signup_requires_invite: true,
+            strip_metadata: true,
+            access_token_ttl: 60 * 60 * 24,
+        }
+    }
+
+    fn assert_thumbnail(
+        thumb_fit: ThumbnailFit,
+        size: (u32, u32),
+        filter: &str,
+        dimensions: (u32, u32),
+    ) {
+        let config = config(thumb_fit);
+        assert_eq!(get_thumbnail_filter(&config, size), filter);
+        assert_eq!(get_thumbnail_dimensions(&config, size), dimensions);
+    }
+
+    #[test]
+    fn fill_thumbnails() {
+        assert_thumbnail(ThumbnailFit::Fill, LANDSCAPE, FILL, (192, 192));
+        assert_thumbnail(ThumbnailFit::Fill, PORTRAIT, FILL, (192, 192));
+    }
+
+    #[test]
+    fn stretch_thumbnails() {
+        assert_thumbnail(
+            ThumbnailFit::Stretch,
+            LANDSCAPE,
+            "scale=192:192,thumbnail",
+            (192, 192),
+        );
+        assert_thumbnail(
+            ThumbnailFit::Stretch,
+            PORTRAIT,
+            "scale=192:192,thumbnail",
+            (192, 192),
+        );
+    }
+
+    #[test]
+    fn fit_blur_thumbnails() {
+        assert_thumbnail(ThumbnailFit::FitBlur, LANDSCAPE, FIT_BLUR, (192, 192));
+        assert_thumbnail(ThumbnailFit::FitBlur, PORTRAIT, FIT_BLUR, (192, 192));
+    }
+
+    #[test]
+    fn fit_blur_portrait_thumbnails() {
+        // wide content is cropped, tall content is letterboxed
+        assert_thumbnail(ThumbnailFit::FitBlurPortrait, LANDSCAPE, FILL, (192, 192));
+        assert_thumbnail(
+            ThumbnailFit::FitBlurPortrait,
+            PORTRAIT,
+            FIT_BLUR,
+            (192, 192),
+        );
+    }
+
+    fn fixture_path(name: &str) -> String {
+        format!(
+            "{}/tests/fixtures/thumbnails/{}.ppm",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        )
+    }
+
+    /// A binary PPM image, the format ffmpeg writes the rendered thumbnails in.
+    struct Ppm {
+        width: u32,
+        height: u32,
+        pixels: Vec<u8>,
+    }
+
+    impl Ppm {
+        fn parse(bytes: &[u8]) -> Ppm {
+            // the header is the magic number, width, height and max value, each followed by whitespace
+            let mut fields = Vec::new();
+            let mut start = 0;
+            for (i, b) in bytes.iter().enumerate() {
+                if b.is_ascii_whitespace() {
+                    fields.push(std::str::from_utf8(&bytes[start..i]).unwrap());
+                    start = i + 1;
+                    if fields.len() == 4 {
+                        break;
+                    }
+                }
+            }
+            assert_eq!(fields[0], "P6", "Not a binary PPM");
+
+            Ppm {
+                width: fields[1].parse().unwrap(),
+                height: fields[2].parse().unwrap(),
+                pixels: bytes[start..].to_vec(),
+            }
+        }
+    }
+
+    /// Renders a fixture into a 64x64 thumbnail box, returning the thumbnail and the size
+    /// [get_thumbnail_dimensions] reports for it.
+    async fn render_thumbnail(thumb_fit: ThumbnailFit, fixture: &str) -> (Ppm, (u32, u32)) {
+        let mut config = config(thumb_fit);
+        config.thumb_width = 64;
+        config.thumb_height = 64;
+
+        let bytes = std::fs::read(fixture_path(fixture)).unwrap();
+        let source = Ppm::parse(&bytes);
+        let size = (source.width, source.height);
+
+        let mut content = NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut content, &bytes).unwrap();
+
+        let filter = get_thumbnail_filter(&config, size);
+        let output_args = [
+            "-vframes", "1", "-pix_fmt", "rgb24", "-c:v", "ppm", "-f", "image2",
+        ];
+        let out = run_ffmpeg(&content, &[], &["-vf", &filter], &output_args)
+            .await
+            .unwrap();
+
+        let thumb = Ppm::parse(&std::fs::read(out.path()).unwrap());
+        (thumb, get_thumbnail_dimensions(&config, size))
+    }
+
+    /// Renders a fixture and compares it to a golden image. Scaling blends the colors where the
+    /// fixtures' stripes meet, so the images only have to be close.
+    async fn assert_golden(thumb_fit: ThumbnailFit, fixture: &str, golden: &str) {
+        let (thumb, dimensions) = render_thumbnail(thumb_fit, fixture).await;
+        let expected = Ppm::parse(&std::fs::read(fixture_path(golden)).unwrap());
+
+        assert_eq!(
+            (thumb.width, thumb.height),
+            (expected.width, expected.height)
+        );
+        assert_eq!((thumb.width, thumb.height), dimensions);
+        assert_eq!(thumb.pixels.len(), expected.pixels.len());
+
+        let difference: u64 = thumb
+            .pixels
+            .iter()
+            .zip(&expected.pixels)
+            .map(|(a, b)| a.abs_diff(*b) as u64)
+            .sum();
+        let mean = difference as f64 / thumb.pixels.len() as f64;
+        assert!(
+            mean < 12.0,
+            "{} thumbnail differs from {} by {:.1} per channel",
+            fixture,
+            golden,
+            mean
+        );
+    }
+
+    // the fixtures are 2:1 images striped green, red and blue in a 1:2:1 ratio, so cropping
+    // to fill leaves only red, and the blurred background of a letterbox is red as well
+
+    #[actix_web::test]
+    async fn fit_thumbnail_images() {
+        // fit scales the content, so the thumbnail keeps its aspect ratio
+        for fixture in ["landscape", "portrait"] {
+            let (thumb, dimensions) = render_thumbnail(ThumbnailFit::Fit, fixture).await;
+            assert_eq!((thumb.width, thumb.height), dimensions);
+
+            let source = Ppm::parse(&std::fs::read(fixture_path(fixture)).unwrap());
+            assert_eq!(
+                thumb.width * source.height,
+                thumb.height * source.width,
+                "{} thumbnail doesn't keep its aspect ratio",
+                fixture
+            );
+        }
+    }
+
+    #[actix_web::test]
+    async fn fill_thumbnail_images() {
+        assert_golden(ThumbnailFit::Fill, "landscape", "fill").await;
+        assert_golden(ThumbnailFit::Fill, "portrait", "fill").await;
+    }
+
+    #[actix_web::test]
+    async fn stretch_thumbnail_images() {
+        assert_golden(ThumbnailFit::Stretch, "landscape", "stretch_landscape").await;
+        assert_golden(ThumbnailFit::Stretch, "portrait", "stretch_portrait").await;
+    }
+
+    #[actix_web::test]
+    async fn fit_blur_thumbnail_images() {
+        assert_golden(ThumbnailFit::FitBlur, "landscape", "fit_blur_landscape").await;
+        assert_golden(ThumbnailFit::FitBlur, "portrait", "fit_blur_portrait").await;
+    }
+
+    #[actix_web::test]
+    async fn fit_blur_portrait_thumbnail_images() {
+        assert_golden(ThumbnailFit::FitBlurPortrait, "landscape", "fill").await;
+        assert_golden(
+            ThumbnailFit::FitBlurPortrait,
+            "portrait",
+            "fit_blur_portrait",
+        )
+        .await;
+    }
+}
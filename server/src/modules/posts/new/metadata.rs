@@ -0,0 +1,162 @@
+const JPEG_SOI: &[u8] = b"\xff\xd8";
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+// EXIF tag for how the image should be rotated when displayed
+const ORIENTATION_TAG: u16 = 0x0112;
+
+/// PNG chunks that only hold text, timestamps or EXIF data.
+const PNG_METADATA_CHUNKS: [&[u8]; 5] = [b"tEXt", b"zTXt", b"iTXt", b"eXIf", b"tIME"];
+
+/// Strips identifying metadata, like GPS coordinates and camera serial numbers, from a JPEG or PNG.
+/// Only what's needed to display the image correctly is kept.
+/// Returns None for other formats, files that can't be parsed, or when there was nothing to strip.
+pub fn strip_metadata(bytes: &[u8]) -> Option<Vec<u8>> {
+    let stripped = if bytes.starts_with(JPEG_SOI) {
+        strip_jpeg(bytes)?
+    } else if bytes.starts_with(PNG_SIGNATURE) {
+        strip_png(bytes)?
+    } else {
+        return None;
+    };
+
+    (stripped != bytes).then_some(stripped)
+}
+
+fn read_u16(bytes: &[u8], pos: usize, big_endian: bool) -> Option<u16> {
+    let b: [u8; 2] = bytes.get(pos..pos + 2)?.try_into().ok()?;
+    Some(match big_endian {
+        true => u16::from_be_bytes(b),
+        false => u16::from_le_bytes(b),
+    })
+}
+
+fn read_u32(bytes: &[u8], pos: usize, big_endian: bool) -> Option<u32> {
+    let b: [u8; 4] = bytes.get(pos..pos + 4)?.try_into().ok()?;
+    Some(match big_endian {
+        true => u32::from_be_bytes(b),
+        false => u32::from_le_bytes(b),
+    })
+}
+
+/// Finds the orientation in the first IFD of an EXIF block, which starts with `Exif\0\0`.
+fn exif_orientation(exif: &[u8]) -> Option<u16> {
+    let tiff = exif.get(6..)?;
+    let big_endian = match tiff.get(0..2)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+
+    let ifd = read_u32(tiff, 4, big_endian)? as usize;
+    let count = read_u16(tiff, ifd, big_endian)? as usize;
+
+    (0..count)
+        .map(|i| ifd + 2 + i * 12)
+        .find(|entry| read_u16(tiff, *entry, big_endian) == Some(ORIENTATION_TAG))
+        .and_then(|entry| read_u16(tiff, entry + 8, big_endian))
+}
+
+/// Builds an APP1 segment holding nothing but the orientation.
+fn orientation_segment(orientation: u16) -> Vec<u8> {
+    let mut payload: Vec<u8> = Vec::new();
+    payload.extend(b"Exif\0\0");
+    // big endian TIFF header, with the first IFD right after it
+    payload.extend(b"MM\0\x2a\0\0\0\x08");
+    payload.extend(1u16.to_be_bytes());
+    payload.extend(ORIENTATION_TAG.to_be_bytes());
+    // a single SHORT value, padded out to the 4 byte value field
+    payload.extend(3u16.to_be_bytes());
+    payload.extend(1u32.to_be_bytes());
+    payload.extend(orientation.to_be_bytes());
+    payload.extend([0, 0]);
+    // no next IFD
+    payload.extend(0u32.to_be_bytes());
+
+    let mut segment = vec![0xff, 0xe1];
+    segment.extend(((payload.len() + 2) as u16).to_be_bytes());
+    segment.extend(payload);
+    segment
+}
+
+/// Whether a JPEG APPn or comment segment is needed to display the image.
+fn keep_jpeg_segment(marker: u8, data: &[u8]) -> bool {
+    match marker {
+        // JFIF header
+        0xe0 => data.starts_with(b"JFIF\0"),
+        // color profile
+        0xe2 => data.starts_with(b"ICC_PROFILE\0"),
+        // Adobe color transform, needed to decode CMYK images
+        0xee => data.starts_with(b"Adobe"),
+        // EXIF, XMP, IPTC, comments and everything else
+        0xe1..=0xef | 0xfe => false,
+        _ => true,
+    }
+}
+
+fn strip_jpeg(bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut result = JPEG_SOI.to_vec();
+    let mut pos = 2;
+
+    loop {
+        if *bytes.get(pos)? != 0xff {
+            return None;
+        }
+
+        // markers can be padded with any number of 0xff bytes
+        while *bytes.get(pos + 1)? == 0xff {
+            pos += 1;
+        }
+        let marker = bytes[pos + 1];
+
+        match marker {
+            // start of the image data or end of image, everything after is kept as is
+            0xda | 0xd9 => {
+                result.extend(&bytes[pos..]);
+                return Some(result);
+            }
+            // markers without a length
+            0x01 | 0xd0..=0xd7 => {
+                result.extend(&bytes[pos..pos + 2]);
+                pos += 2;
+            }
+            _ => {
+                let length = read_u16(bytes, pos + 2, true)? as usize;
+                if length < 2 {
+                    return None;
+                }
+                let segment = bytes.get(pos..pos + 2 + length)?;
+                let data = &segment[4..];
+
+                if keep_jpeg_segment(marker, data) {
+                    result.extend(segment);
+                } else if marker == 0xe1 && data.starts_with(b"Exif\0\0") {
+                    // without the orientation, photos taken sideways would be shown sideways
+                    if let Some(orientation) = exif_orientation(data).filter(|o| *o != 1) {
+                        result.extend(orientation_segment(orientation));
+                    }
+                }
+
+                pos += 2 + length;
+            }
+        }
+    }
+}
+
+fn strip_png(bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut result = PNG_SIGNATURE.to_vec();
+    let mut pos = PNG_SIGNATURE.len();
+
+    while pos < bytes.len() {
+        // length, type, data and CRC
+        let length = read_u32(bytes, pos, true)? as usize;
+        let chunk = bytes.get(pos..pos + 12 + length)?;
+
+        if !PNG_METADATA_CHUNKS.contains(&&chunk[4..8]) {
+            result.extend(chunk);
+        }
+
+        pos += 12 + length;
+    }
+
+    Some(result)
+}
@@ -1,7 +1,9 @@
 pub mod api;
 mod media;
+mod metadata;
 mod process;
 mod schema;
 mod upload;
 
 pub use media::UploadInfo;
+pub use upload::{probe_original, regenerate_renditions};
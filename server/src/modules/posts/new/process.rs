@@ -5,16 +5,24 @@ use tempfile::NamedTempFile;
 
 use super::schema::PostNewSchema;
 use crate::{booru_config::BooruConfig, util::http::create_client};
-use std::io::Write;
+use std::io::{Read, Seek, SeekFrom, Write};
 
 use super::media::{get_content_info, UploadInfo};
+use super::metadata::strip_metadata;
 
 type FileProcessResult = Result<(String, UploadInfo, NamedTempFile), (String, String)>;
 
 // returns true if this upload is unique, false if the hash exists
-async fn check_upload_unique(db: &MySqlPool, hash: String) -> Result<bool, String> {
-    sqlx::query("SELECT 1 FROM images WHERE hash = ?")
-        .bind(hash)
+async fn check_upload_unique(db: &MySqlPool, info: &UploadInfo) -> Result<bool, String> {
+    // either hash can be in either column: posts uploaded before metadata was stripped have the
+    // original file's hash as their hash, and stripped posts keep it as their original_hash
+    let original_hash = info.original_hash.as_ref().unwrap_or(&info.hash);
+
+    sqlx::query("SELECT 1 FROM images WHERE hash IN (?, ?) OR original_hash IN (?, ?) LIMIT 1")
+        .bind(&info.hash)
+        .bind(original_hash)
+        .bind(&info.hash)
+        .bind(original_hash)
         .fetch_one(db)
         .await
         .map_or_else(
@@ -29,6 +37,48 @@ async fn check_upload_unique(db: &MySqlPool, hash: String) -> Result<bool, Strin
         )
 }
 
+/// Strips identifying metadata from an uploaded file in place.
+/// Returns the hash of the file as it was uploaded if anything was removed.
+fn strip_upload_metadata(temp: &mut NamedTempFile) -> Result<Option<String>, String> {
+    let mut bytes = Vec::new();
+    temp.reopen()
+        .and_then(|mut f| f.read_to_end(&mut bytes))
+        .map_err(|e| {
+            error!("Error reading temp file: {:?}", e);
+            "Temp file error".to_owned()
+        })?;
+
+    let Some(stripped) = strip_metadata(&bytes) else {
+        return Ok(None);
+    };
+
+    temp.as_file_mut()
+        .set_len(0)
+        .and_then(|_| temp.seek(SeekFrom::Start(0)))
+        .and_then(|_| temp.write_all(&stripped))
+        .map_err(|e| {
+            error!("Error writing stripped file: {:?}", e);
+            "Temp file error".to_owned()
+        })?;
+
+    Ok(Some(format!("{:x}", md5::compute(&bytes))))
+}
+
+/// Gets information on an uploaded file, after stripping its metadata if the board wants that.
+async fn get_upload_info(
+    config: &BooruConfig,
+    temp: &mut NamedTempFile,
+) -> Result<UploadInfo, String> {
+    let original_hash = match config.strip_metadata {
+        true => strip_upload_metadata(temp)?,
+        false => None,
+    };
+
+    let mut info = get_content_info(temp).await?;
+    info.original_hash = original_hash;
+    Ok(info)
+}
+
 pub async fn process_url_upload(
     db: &MySqlPool,
     config: BooruConfig,
@@ -92,11 +142,11 @@ pub async fn process_url_upload(
         })?;
     }
 
-    let info = get_content_info(&temp)
+    let info = get_upload_info(&config, &mut temp)
         .await
         .map_err(|e| (filename.clone(), e))?;
 
-    if !check_upload_unique(db, info.hash.clone())
+    if !check_upload_unique(db, &info)
         .await
         .map_err(|e| (filename.clone(), e))?
     {
@@ -140,11 +190,11 @@ pub async fn process_file_upload(
         (filename.clone(), "Temp file error".to_owned())
     })?;
 
-    let info = get_content_info(&temp)
+    let info = get_upload_info(&config, &mut temp)
         .await
         .map_err(|e| (filename.clone(), e))?;
 
-    if !check_upload_unique(db, info.hash.clone())
+    if !check_upload_unique(db, &info)
         .await
         .map_err(|e| (filename.clone(), e))?
     {
@@ -179,3 +229,79 @@ pub async fn process_upload(
         )),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::posts::new::media::UploadFileType;
+
+    /// A PNG chunk, with a CRC that nothing here checks.
+    fn png_chunk(kind: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend(kind);
+        chunk.extend(data);
+        chunk.extend([0, 0, 0, 0]);
+        chunk
+    }
+
+    fn upload_info(hash: String, original_hash: Option<String>) -> UploadInfo {
+        UploadInfo {
+            file_type: UploadFileType::Image,
+            mime: "image/png".to_owned(),
+            length: None,
+            width: 1,
+            height: 1,
+            filesize: 0,
+            hash,
+            has_audio: false,
+            lossless: Some(true),
+            video_codec: None,
+            frame_rate: None,
+            bit_depth: None,
+            original_hash,
+        }
+    }
+
+    #[sqlx::test]
+    async fn stripped_upload_matches_post_stored_before_stripping(db: MySqlPool) {
+        let mut bytes = b"\x89PNG\r\n\x1a\n".to_vec();
+        bytes.extend(png_chunk(b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]));
+        bytes.extend(png_chunk(b"tEXt", b"Comment\0taken at home"));
+        bytes.extend(png_chunk(b"IDAT", &[0]));
+        bytes.extend(png_chunk(b"IEND", &[]));
+
+        let mut temp = NamedTempFile::new().unwrap();
+        temp.write_all(&bytes).unwrap();
+        let original_hash = strip_upload_metadata(&mut temp)
+            .unwrap()
+            .expect("Nothing was stripped");
+        let stripped_hash = format!("{:x}", md5::compute(std::fs::read(temp.path()).unwrap()));
+        assert_eq!(original_hash, format!("{:x}", md5::compute(&bytes)));
+        assert_ne!(original_hash, stripped_hash);
+
+        let info = upload_info(stripped_hash, Some(original_hash.clone()));
+        assert!(check_upload_unique(&db, &info).await.unwrap());
+
+        // posts from before metadata was stripped have the original file's hash and no original_hash
+        let owner_id = sqlx::query("INSERT INTO users (name) VALUES ('uploader')")
+            .execute(&db)
+            .await
+            .unwrap()
+            .last_insert_id();
+        sqlx::query(
+            "INSERT INTO images (owner_id, owner_ip, filename, filesize, hash, ext, width, height) VALUES (?, '127.0.0.1', 'old.png', ?, ?, 'png', 1, 1)",
+        )
+        .bind(owner_id)
+        .bind(bytes.len() as i32)
+        .bind(&original_hash)
+        .execute(&db)
+        .await
+        .unwrap();
+
+        assert!(!check_upload_unique(&db, &info).await.unwrap());
+        // and the original file itself, from a board that doesn't strip metadata
+        assert!(!check_upload_unique(&db, &upload_info(original_hash, None))
+            .await
+            .unwrap());
+    }
+}
@@ -53,6 +53,8 @@ pub struct PostNewSchema {
     pub upload_type: String,
     pub filename: String,
     pub pool_index: Option<i32>,
+    // where the content was found. defaults to the downloaded URL for "url" uploads
+    pub source: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -1,15 +1,17 @@
-use std::io::Read;
+use std::io::{Read, Write};
 use std::sync::Arc;
 
 use log::error;
 use sqlx::MySqlPool;
 use tempfile::NamedTempFile;
 
-use super::media::{create_thumbnail, UploadInfo};
+use super::media::{create_renditions, get_content_info, RenditionFile, UploadInfo};
 use crate::booru_config::BooruConfig;
+use crate::error::ApiError;
 
 use crate::modules::posts::edit::set_post_tags;
 use crate::modules::posts::model::PostResponse;
+use crate::modules::posts::renditions::{fetch_renditions, save_renditions, PostRendition};
 use crate::storage::AppStorage;
 
 pub struct OwnerContext {
@@ -20,7 +22,8 @@ pub struct OwnerContext {
 struct PostRemoteContentHandler {
     hash: String,
     storage: Arc<AppStorage>,
-    thumb_uploaded: bool,
+    /// Paths of the renditions uploaded so far
+    renditions_uploaded: Vec<String>,
     image_uploaded: bool,
 }
 
@@ -42,7 +45,7 @@ impl PostRemoteContentHandler {
     pub fn new(hash: String, storage: &Arc<AppStorage>) -> PostRemoteContentHandler {
         PostRemoteContentHandler {
             hash,
-            thumb_uploaded: false,
+            renditions_uploaded: Vec::new(),
             image_uploaded: false,
             storage: storage.clone(),
         }
@@ -53,7 +56,7 @@ impl PostRemoteContentHandler {
             .put_image(self.hash.clone(), &file_to_bytes(file)?)
             .await
             .map_err(|e| {
-                error!("S3 error: {:?}", e);
+                error!("Storage error: {:?}", e);
                 "Unable to upload image".to_owned()
             })?;
 
@@ -61,17 +64,17 @@ impl PostRemoteContentHandler {
         Ok(())
     }
 
-    pub async fn upload_thumb(&mut self, file: &NamedTempFile) -> Result<(), String> {
-        self.storage
-            .put_thumb(self.hash.clone(), &file_to_bytes(file)?)
-            .await
-            .map_err(|e| {
-                error!("S3 error: {:?}", e);
-                "Unable to upload thumbnail".to_owned()
-            })?;
-
-        self.thumb_uploaded = true;
-        Ok(())
+    pub async fn upload_renditions(
+        &mut self,
+        renditions: &[RenditionFile],
+    ) -> Result<Vec<PostRendition>, String> {
+        upload_renditions(
+            &*self.storage,
+            self.hash.clone(),
+            renditions,
+            &mut self.renditions_uploaded,
+        )
+        .await
     }
 
     pub async fn undo(&self) -> Result<(), String> {
@@ -80,39 +83,71 @@ impl PostRemoteContentHandler {
                 .delete_file(self.storage.image_path(self.hash.clone()))
                 .await
                 .map_err(|e| {
-                    error!("S3 error while deleting: {:?}", e);
+                    error!("Storage error while deleting: {:?}", e);
                     "Error reversing changes".to_owned()
                 })?;
         }
 
-        if self.thumb_uploaded {
-            self.storage
-                .delete_file(self.storage.thumb_path(self.hash.clone()))
-                .await
-                .map_err(|e| {
-                    error!("S3 error while deleting: {:?}", e);
-                    "Error reversing changes".to_owned()
-                })?;
+        for path in &self.renditions_uploaded {
+            self.storage.delete_file(path.clone()).await.map_err(|e| {
+                error!("Storage error while deleting: {:?}", e);
+                "Error reversing changes".to_owned()
+            })?;
         }
 
         Ok(())
     }
 }
 
+/// Uploads encoded renditions, recording each path in `uploaded` as it goes.
+async fn upload_renditions(
+    storage: &AppStorage,
+    hash: String,
+    renditions: &[RenditionFile],
+    uploaded: &mut Vec<String>,
+) -> Result<Vec<PostRendition>, String> {
+    let mut result = Vec::with_capacity(renditions.len());
+
+    for r in renditions {
+        let bytes = file_to_bytes(&r.file)?;
+        storage
+            .put_rendition(hash.clone(), r.rendition, r.format, &bytes)
+            .await
+            .map_err(|e| {
+                error!("Storage error: {:?}", e);
+                "Unable to upload thumbnail".to_owned()
+            })?;
+        uploaded.push(storage.rendition_path(hash.clone(), r.rendition, r.format));
+
+        let mut rendition = PostRendition::new(
+            r.rendition,
+            r.format,
+            r.width as i32,
+            r.height as i32,
+            bytes.len() as i32,
+        );
+        rendition.storyboard = r.storyboard;
+        result.push(rendition);
+    }
+
+    Ok(result)
+}
+
 type PostCreateResult = Result<(String, PostResponse), (String, String)>;
 
-async fn upload_and_create_with_thumb(
+async fn upload_and_create_with_renditions(
     db: &MySqlPool,
     tags: Vec<String>,
+    source: Option<String>,
     owner: OwnerContext,
     handler: &mut PostRemoteContentHandler,
     content_file: &NamedTempFile,
-    thumb_file: &NamedTempFile,
+    rendition_files: &[RenditionFile],
     filename: String,
     info: &UploadInfo,
 ) -> Result<PostResponse, String> {
     handler.upload_image(&content_file).await?;
-    handler.upload_thumb(&thumb_file).await?;
+    let renditions = handler.upload_renditions(rendition_files).await?;
 
     let is_video = match info.is_video() {
         true => 1,
@@ -122,12 +157,17 @@ async fn upload_and_create_with_thumb(
         true => 1,
         false => 0,
     };
+    let has_audio = match info.has_audio {
+        true => 1,
+        false => 0,
+    };
+    let lossless = info.lossless.map(|l| l as i8);
 
     let response = sqlx::query!(
         r#"
 		INSERT INTO images 
-		(`owner_id`, `owner_ip`, `filename`, `filesize`, `hash`, `ext`, `width`, `height`, `video`, `image`, `length`, `mime`)
-		VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+		(`owner_id`, `owner_ip`, `filename`, `filesize`, `hash`, `ext`, `width`, `height`, `video`, `image`, `audio`, `lossless`, `video_codec`, `frame_rate`, `bit_depth`, `length`, `mime`, `original_hash`, `source`)
+		VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
         owner.owner_id,
         owner.owner_ip,
         filename,
@@ -138,9 +178,16 @@ async fn upload_and_create_with_thumb(
         info.height,
         is_video,
         is_image,
+        has_audio,
+        lossless,
+        info.video_codec,
+        info.frame_rate,
+        info.bit_depth,
 		// adjust to milliseconds
         info.length.and_then(|l| Some(l * 1000)),
-        info.mime
+        info.mime,
+        info.original_hash,
+        source
     )
 	.execute(db)
 	.await
@@ -150,7 +197,13 @@ async fn upload_and_create_with_thumb(
 	})?;
 
     let post_id = response.last_insert_id().to_string();
-    let post_result = set_post_tags(db, owner.owner_id, post_id.clone(), tags).await;
+    let post_result: Result<PostResponse, ApiError> = async {
+        save_renditions(db, response.last_insert_id() as i32, &renditions).await?;
+        // the post starts without tags, so this also records the initial tag history entry,
+        // with every tag added by the uploader
+        set_post_tags(db, owner.owner_id, post_id.clone(), tags).await
+    }
+    .await;
 
     match post_result {
         Err(e) => {
@@ -171,6 +224,7 @@ async fn upload_and_create_with_thumb(
 pub async fn upload_and_create_post(
     db: &MySqlPool,
     tags: Vec<String>,
+    source: Option<String>,
     owner: OwnerContext,
     storage: &Arc<AppStorage>,
     config: BooruConfig,
@@ -178,18 +232,19 @@ pub async fn upload_and_create_post(
     temp_file: &NamedTempFile,
     info: &UploadInfo,
 ) -> PostCreateResult {
-    let thumb = create_thumbnail(&config, &temp_file, &info)
+    let renditions = create_renditions(&config, &temp_file, &info)
         .await
         .map_err(|e| (filename.clone(), e))?;
 
     let mut handler = PostRemoteContentHandler::new(info.hash.clone(), storage);
-    let res = upload_and_create_with_thumb(
+    let res = upload_and_create_with_renditions(
         db,
         tags,
+        source,
         owner,
         &mut handler,
         temp_file,
-        &thumb,
+        &renditions,
         filename.clone(),
         info,
     )
@@ -204,3 +259,66 @@ pub async fn upload_and_create_post(
 
     res
 }
+
+/// Downloads the stored original of a post into a temp file.
+async fn fetch_original(storage: &AppStorage, hash: String) -> Result<NamedTempFile, String> {
+    let content = storage.get_image(hash).await.map_err(|e| {
+        error!("Storage error while fetching original: {:?}", e);
+        "Unable to fetch original".to_owned()
+    })?;
+
+    let mut temp_file = NamedTempFile::new().map_err(|e| {
+        error!("Error creating temp file: {:?}", e);
+        "Temp file error".to_owned()
+    })?;
+    temp_file.write_all(&content).map_err(|e| {
+        error!("Error writing temp file: {:?}", e);
+        "Temp file error".to_owned()
+    })?;
+
+    Ok(temp_file)
+}
+
+/// Reads the media information of an existing post from its stored original.
+pub async fn probe_original(storage: &AppStorage, hash: String) -> Result<UploadInfo, String> {
+    get_content_info(&fetch_original(storage, hash).await?).await
+}
+
+/// Creates new renditions for an existing post from its stored original, replacing the old ones.
+pub async fn regenerate_renditions(
+    db: &MySqlPool,
+    storage: &AppStorage,
+    config: &BooruConfig,
+    post_id: i32,
+    hash: String,
+) -> Result<(), String> {
+    let temp_file = fetch_original(storage, hash.clone()).await?;
+    let info = get_content_info(&temp_file).await?;
+    let rendition_files = create_renditions(config, &temp_file, &info).await?;
+
+    let previous = fetch_renditions(db, &[post_id])
+        .await
+        .map_err(|e| e.message)?
+        .remove(&post_id)
+        .unwrap_or_default();
+
+    let mut uploaded = Vec::new();
+    let renditions =
+        upload_renditions(storage, hash.clone(), &rendition_files, &mut uploaded).await?;
+
+    save_renditions(db, post_id, &renditions)
+        .await
+        .map_err(|e| e.message)?;
+
+    // clean up renditions the current settings no longer create
+    for old in previous {
+        let path = storage.rendition_path(hash.clone(), old.rendition, old.format);
+        if !uploaded.contains(&path) {
+            if let Err(e) = storage.delete_file(path).await {
+                error!("Storage error while deleting old rendition: {:?}", e);
+            }
+        }
+    }
+
+    Ok(())
+}
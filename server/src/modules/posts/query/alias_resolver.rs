@@ -1,6 +1,6 @@
 use std::collections::{HashMap, HashSet};
 
-use sqlx::MySqlPool;
+use sqlx::{Executor, MySql};
 
 use crate::error::ApiError;
 
@@ -8,7 +8,10 @@ pub struct TagAliasResolver {
     aliases: HashMap<String, String>,
 }
 
-async fn fetch_alias_map(db: &MySqlPool) -> Result<HashMap<String, String>, ApiError> {
+async fn fetch_alias_map<'e, E>(db: E) -> Result<HashMap<String, String>, ApiError>
+where
+    E: Executor<'e, Database = MySql>,
+{
     let result = sqlx::query!("SELECT oldtag, newtag FROM aliases")
         .fetch_all(db)
         .await?;
@@ -19,7 +22,10 @@ async fn fetch_alias_map(db: &MySqlPool) -> Result<HashMap<String, String>, ApiE
 }
 
 impl TagAliasResolver {
-    pub async fn new(db: &MySqlPool) -> Result<TagAliasResolver, ApiError> {
+    pub async fn new<'e, E>(db: E) -> Result<TagAliasResolver, ApiError>
+    where
+        E: Executor<'e, Database = MySql>,
+    {
         let aliases = fetch_alias_map(db).await?;
 
         Ok(TagAliasResolver { aliases })
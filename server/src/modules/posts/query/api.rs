@@ -1,5 +1,6 @@
 use super::alias_resolver::TagAliasResolver;
 use crate::error::{api_error, ApiErrorType};
+use crate::modules::posts::model::PostRating;
 use crate::modules::users::middleware::{get_user, AuthFactory};
 use actix_web::{get, web, HttpRequest, HttpResponse};
 use itertools::Itertools;
@@ -43,28 +44,43 @@ pub async fn post_list_handler(
                 images: false,
                 videos: false,
                 vr: false,
+                ratings: Vec::new(),
             };
             for p in s.split(",") {
                 match p {
                     "images" => filter.images = true,
                     "videos" => filter.videos = true,
                     "vr" => filter.vr = true,
+                    "safe" | "questionable" | "explicit" | "unknown" => {
+                        filter.ratings.extend(PostRating::parse(p))
+                    }
                     _ => {}
                 };
             }
 
+            // a filter with only ratings shouldn't also exclude every content type
+            if !filter.images && !filter.videos && !filter.vr && !filter.ratings.is_empty() {
+                filter.images = true;
+                filter.videos = true;
+                filter.vr = true;
+            }
+
             filter
         })
         .unwrap_or(ContentFilter {
             images: true,
             videos: true,
             vr: true,
+            ratings: Vec::new(),
         });
 
     let query = alias_resolver.resolve(&query);
 
     let parsed_query = ImageQuery::new(query, offset, limit, filter)?;
-    let result = QueryEngine::run(&data.db, parsed_query, user.id).await?;
+    let mut result = QueryEngine::run(&data.db, parsed_query, user.id).await?;
+    for post in result.posts.iter_mut() {
+        post.sign_media_urls(&*data.storage)?;
+    }
 
     Ok(api_success(result))
 }
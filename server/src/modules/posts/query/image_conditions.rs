@@ -7,6 +7,8 @@ use once_cell::sync::Lazy;
 use parse_size::parse_size;
 use serde::{ser::SerializeSeq, Deserialize, Serialize, Serializer};
 
+use crate::modules::posts::model::PostRating;
+use crate::modules::posts::source::normalize_source;
 use crate::util::database::query_object::QueryObject;
 
 bitmask! {
@@ -350,7 +352,9 @@ pub static IMAGE_CONDITIONS: Lazy<Vec<ImageCondition>> = Lazy::new(|| {
             |_op, value| match value {
                 "any" => Some(QueryObject::new_with_query("images.source IS NOT NULL")),
                 "none" => Some(QueryObject::new_with_query("images.source IS NULL")),
-                v => Some(QueryObject::new_with_param("images.source", v)),
+                // sources are stored normalized, so search for the normalized form
+                v => normalize_source(v)
+                    .map(|source| QueryObject::new_with_param("images.source = ?", source)),
             },
         ),
         ImageCondition::new_equals_single(
@@ -625,6 +629,142 @@ pub static IMAGE_CONDITIONS: Lazy<Vec<ImageCondition>> = Lazy::new(|| {
                 ))
             },
         ),
+        ImageCondition::new_equals_single(
+            "rating",
+            "filter by content rating",
+            ConditionUsagePart {
+                placeholder:
+                    "{'safe', 'questionable', 'explicit', 'unknown', or rating letters like 'sq'}",
+                value_type: ConditionValue::Text,
+                example: Some("safe"),
+            },
+            |_op, value| {
+                // either a single rating name, or any combination of rating codes
+                let ratings = match PostRating::parse(value) {
+                    Some(rating) => vec![rating],
+                    None => value
+                        .chars()
+                        .map(|c| PostRating::parse(c.to_string().as_str()))
+                        .collect::<Option<Vec<PostRating>>>()?,
+                };
+
+                let mut query = QueryObject::new_with_query(
+                    format!(
+                        "images.rating IN ({})",
+                        ratings.iter().map(|_| "?").join(", ")
+                    )
+                    .as_str(),
+                );
+                query.push_params(ratings.iter().map(|r| r.code()));
+                Some(query)
+            },
+        ),
+        ImageCondition::new_equals_single(
+            "parent",
+            "filter by parent post",
+            ConditionUsagePart {
+                placeholder: "{post ID, 'any', or 'none'}",
+                value_type: ConditionValue::Text,
+                example: Some("any"),
+            },
+            |_op, value| match value {
+                "any" => Some(QueryObject::new_with_query("images.parent_id IS NOT NULL")),
+                "none" => Some(QueryObject::new_with_query("images.parent_id IS NULL")),
+                v => v
+                    .parse::<i32>()
+                    .ok()
+                    .map(|id| QueryObject::new_with_param("images.parent_id = ?", id)),
+            },
+        ),
+        ImageCondition::new_equals_single(
+            "child",
+            "filter by child post",
+            ConditionUsagePart {
+                placeholder: "{post ID, 'any', or 'none'}",
+                value_type: ConditionValue::Text,
+                example: Some("952"),
+            },
+            |_op, value| match value {
+                "any" => Some(QueryObject::new_with_query("images.has_children = 1")),
+                "none" => Some(QueryObject::new_with_query("images.has_children = 0")),
+                v => v.parse::<i32>().ok().map(|id| {
+                    QueryObject::new_with_param(
+                        "images.id = (SELECT c.parent_id FROM images AS c WHERE c.id = ?)",
+                        id,
+                    )
+                }),
+            },
+        ),
+        ImageCondition::new_equals_single(
+            "has",
+            "filter for posts with a given property",
+            ConditionUsagePart {
+                placeholder: "{'children' or 'audio'}",
+                value_type: ConditionValue::Text,
+                example: Some("children"),
+            },
+            |_op, value| match value {
+                "children" => Some(QueryObject::new_with_query("images.has_children = 1")),
+                "audio" => Some(QueryObject::new_with_query("images.audio = 1")),
+                _ => None,
+            },
+        ),
+        ImageCondition::new_equals_single(
+            "codec",
+            "filter by video codec",
+            ConditionUsagePart {
+                placeholder: "{codec}",
+                value_type: ConditionValue::Text,
+                example: Some("vp9"),
+            },
+            |_op, value| Some(QueryObject::new_with_param("images.video_codec = ?", value)),
+        ),
+        ImageCondition::new_equals_single(
+            "lossless",
+            "filter for images stored with or without lossy compression",
+            ConditionUsagePart {
+                placeholder: "{'true' or 'false'}",
+                value_type: ConditionValue::Text,
+                example: Some("true"),
+            },
+            |_op, value| match value {
+                "true" => Some(QueryObject::new_with_query("images.lossless = 1")),
+                "false" => Some(QueryObject::new_with_query("images.lossless = 0")),
+                _ => None,
+            },
+        ),
+        ImageCondition::new_all_single(
+            "fps",
+            "filter by video frame rate",
+            ConditionUsagePart {
+                placeholder: "{frames per second}",
+                value_type: ConditionValue::Number,
+                example: Some("60"),
+            },
+            |op, value| {
+                // frame rates are stored as floats, so 29.97 would never equal 29.97 otherwise
+                value.parse::<f32>().ok().map(|fps| {
+                    QueryObject::new_with_param(
+                        format!("ROUND(images.frame_rate, 2) {} ?", op).as_str(),
+                        fps,
+                    )
+                })
+            },
+        ),
+        ImageCondition::new_all_single(
+            "bitdepth",
+            "filter by bits per color channel",
+            ConditionUsagePart {
+                placeholder: "{bits}",
+                value_type: ConditionValue::Integer,
+                example: Some("10"),
+            },
+            |op, value| {
+                value.parse::<u8>().ok().map(|bits| {
+                    QueryObject::new_with_param(format!("images.bit_depth {} ?", op).as_str(), bits)
+                })
+            },
+        ),
     ]
 });
 
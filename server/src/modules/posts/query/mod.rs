@@ -2,5 +2,5 @@ pub mod alias_resolver;
 pub mod api;
 pub mod image_conditions;
 pub mod model;
-mod parser;
-mod query_engine;
+pub mod parser;
+pub mod query_engine;
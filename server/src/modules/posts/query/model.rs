@@ -4,7 +4,14 @@ use sqlx::MySqlPool;
 
 use crate::{
     error::ApiError,
-    modules::{pools::model::PoolResponse, posts::model::PostModel},
+    modules::{
+        pools::model::PoolResponse,
+        posts::{
+            model::{PostModel, PostRating},
+            renditions::{fetch_renditions, PostRendition},
+        },
+    },
+    storage::AppStorage,
 };
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -23,13 +30,25 @@ pub struct PostQueryResult {
     pub tags: Vec<String>,
     pub pools: Vec<i32>,
     pub views: i32,
+    pub parent_id: Option<i32>,
+    pub has_children: bool,
+    pub rating: PostRating,
+    pub locked: bool,
+    /// Short-lived signed URLs to the post's content, see [PostQueryResult::sign_media_urls]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumb_url: Option<String>,
+    /// Downscaled copies of the content, smallest first
+    pub renditions: Vec<PostRendition>,
 }
 
 impl PostQueryResult {
     pub fn from_model(
         model: PostModel,
         tags: Vec<String>,
-        pools: Vec<i32>
+        pools: Vec<i32>,
+        renditions: Vec<PostRendition>,
     ) -> Result<PostQueryResult, ApiError> {
         Ok(PostQueryResult {
             id: model.id,
@@ -45,10 +64,26 @@ impl PostQueryResult {
             numeric_score: model.numeric_score,
             tags,
             pools,
-            views: model.views
+            views: model.views,
+            parent_id: model.parent_id,
+            has_children: model.has_children != 0,
+            rating: PostRating::parse(&model.rating).unwrap_or(PostRating::Unknown),
+            locked: model.locked != 0,
+            image_url: None,
+            thumb_url: None,
+            renditions,
         })
     }
 
+    pub fn sign_media_urls(&mut self, storage: &AppStorage) -> Result<(), ApiError> {
+        self.image_url = Some(storage.signed_image_url(self.hash.clone())?);
+        self.thumb_url = Some(storage.signed_thumb_url(self.hash.clone())?);
+        for rendition in self.renditions.iter_mut() {
+            rendition.sign_url(storage, self.hash.clone())?;
+        }
+        Ok(())
+    }
+
     pub async fn from_model_query(
         model: PostModel,
         db: &MySqlPool,
@@ -68,7 +103,12 @@ impl PostQueryResult {
                 .await?;
         let pools = pool_result.iter().map(|(p,)| p.to_owned()).collect_vec();
 
-        PostQueryResult::from_model(model, tags, pools)
+        let renditions = fetch_renditions(db, &[model.id])
+            .await?
+            .remove(&model.id)
+            .unwrap_or_default();
+
+        PostQueryResult::from_model(model, tags, pools, renditions)
     }
 }
 
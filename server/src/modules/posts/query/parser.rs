@@ -6,15 +6,24 @@ use regex::Regex;
 use serde::{Deserialize, Serialize};
 
 use crate::error::ApiError;
+use crate::modules::posts::model::PostRating;
 
 use super::image_conditions::{Operator, Operators, IMAGE_CONDITIONS_MAP};
 
 use crate::util::database::query_object::QueryObject;
 
+// values can have a decimal point, for conditions like fps:29.97
 static IMAGE_CONDITION_REGEX: Lazy<Regex> = Lazy::new(|| {
-    Regex::new(r"^([a-z_]+?)(\=|\>|\<|\<\=|\>\=|\:)([a-z0-9_]+?)(_([a-z0-9_]+?))?$").unwrap()
+    Regex::new(r"^([a-z_]+?)(\=|\>|\<|\<\=|\>\=|\:)([a-z0-9_.]+?)(_([a-z0-9_]+?))?$").unwrap()
 });
 
+// text conditions take everything after the separator as-is, so values like URLs survive
+static TEXT_CONDITION_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^([a-zA-Z_]+)(\=|\:)(.+)$").unwrap());
+
+// conditions whose values are matched as written, every other condition is lowercased first
+const TEXT_CONDITIONS: [&str; 2] = ["source", "filename"];
+
 fn default_as_true() -> bool {
     return true;
 }
@@ -27,6 +36,9 @@ pub struct ContentFilter {
     pub videos: bool,
     #[serde(default = "default_as_true")]
     pub vr: bool,
+    /// Only include posts with these ratings, or every rating if empty
+    #[serde(default)]
+    pub ratings: Vec<PostRating>,
 }
 
 pub struct ImageQuery {
@@ -38,6 +50,13 @@ pub struct ImageQuery {
 }
 
 impl ImageQuery {
+    /// Whether anything has to be true of a post for it to match, as opposed to only things that can't be.
+    /// Queries without any match nearly every post.
+    pub fn has_positive_condition(&self) -> bool {
+        self.tag_conditions.iter().any(|(_, positive)| *positive)
+            || self.img_conditions.iter().any(|(_, positive)| *positive)
+    }
+
     fn parse_image_condition(
         name: &str,
         op: &str,
@@ -63,6 +82,19 @@ impl ImageQuery {
         return (condition.to_query)(&operators.to_str(), value);
     }
 
+    fn parse_text_condition(tag: &str) -> Option<QueryObject> {
+        let captures = TEXT_CONDITION_REGEX.captures(tag)?;
+        let name = captures[1].to_lowercase();
+        if !TEXT_CONDITIONS.contains(&name.as_str()) {
+            return None;
+        }
+
+        let condition = IMAGE_CONDITIONS_MAP.get(name.as_str())?;
+
+        let operators: Operators = Operator::Equals.into();
+        (condition.to_query)(&operators.to_str(), &captures[3])
+    }
+
     fn parse_order(column: &str, param: &str) -> String {
         // the regex should insure that param and column are both alphanumeric so can't be used for injection, but i don't trust it...
         let param = match param.chars().all(|c| c.is_alphanumeric()) {
@@ -121,6 +153,13 @@ impl ImageQuery {
             filter_tags.push("-vr".to_owned());
         }
 
+        if !filter.ratings.is_empty() {
+            filter_tags.push(format!(
+                "rating:{}",
+                filter.ratings.iter().map(|r| r.code()).join("")
+            ));
+        }
+
         tags.append(&mut filter_tags);
 
         let mut tags_map: BTreeMap<String, bool> = BTreeMap::new();
@@ -137,6 +176,12 @@ impl ImageQuery {
         let mut img_conditions: Vec<(QueryObject, bool)> = Vec::new();
 
         for (tag, positive) in &tags_map {
+            if let Some(query) = ImageQuery::parse_text_condition(tag) {
+                img_conditions.push((query, *positive));
+                tags_to_remove.push(tag.clone());
+                continue;
+            }
+
             let lower = tag.to_lowercase();
             if let Some(captures) = IMAGE_CONDITION_REGEX.captures(lower.as_str()) {
                 let groups = captures.iter().collect_vec();
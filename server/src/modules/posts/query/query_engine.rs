@@ -9,8 +9,10 @@ use crate::error::{api_error, ApiError, ApiErrorType};
 use crate::modules::pools::model::{PoolModel, PoolResponse};
 
 use super::super::model::PostModel;
+use super::super::renditions::fetch_renditions;
 
-use super::parser::ImageQuery;
+use super::alias_resolver::TagAliasResolver;
+use super::parser::{ContentFilter, ImageQuery};
 
 use crate::util::database::query_object::QueryObject;
 
@@ -88,10 +90,14 @@ impl QueryEngine {
                 }
             }
 
+            let post_ids = results.iter().map(|p| p.id).collect_vec();
+            let mut post_renditions_map = fetch_renditions(db, &post_ids).await?;
+
             for post in results {
                 let tags = post_tags_map.remove(&post.id).unwrap_or_default();
                 let pools = post_pools_map.remove(&post.id).unwrap_or_default();
-                safe_results.push(PostQueryResult::from_model(post, tags, pools)?);
+                let renditions = post_renditions_map.remove(&post.id).unwrap_or_default();
+                safe_results.push(PostQueryResult::from_model(post, tags, pools, renditions)?);
             }
         }
 
@@ -133,6 +139,49 @@ impl QueryEngine {
         })
     }
 
+    /// Finds the IDs of every post matching the query, ignoring its limit and offset.
+    pub async fn find_ids(db: &MySqlPool, image_query: &ImageQuery) -> Result<Vec<i32>, ApiError> {
+        let query_object = QueryEngine::build_query(
+            db,
+            &image_query.tag_conditions,
+            &image_query.img_conditions,
+            image_query.order.clone(),
+            None,
+            None,
+        )
+        .await?;
+
+        let query_str = format!("SELECT tbl.id FROM ({}) AS tbl", query_object.to_string());
+
+        let mut query = sqlx::query_as::<_, (i32,)>(query_str.as_str());
+        for p in &query_object.parameters {
+            query = query.bind(p);
+        }
+
+        let ids = query.fetch_all(db).await?;
+        Ok(ids.iter().map(|(id,)| *id).collect_vec())
+    }
+
+    /// Parses a search query string without any content filters, resolving tag aliases first.
+    pub async fn parse_query(db: &MySqlPool, query: &str) -> Result<ImageQuery, ApiError> {
+        let resolver = TagAliasResolver::new(db).await?;
+        let tags = resolver.resolve(&query.split(" ").map(|s| s.to_owned()).collect_vec());
+
+        let filter = ContentFilter {
+            images: true,
+            videos: true,
+            vr: true,
+            ratings: Vec::new(),
+        };
+        ImageQuery::new(tags, 0, 0, filter)
+    }
+
+    /// Finds the IDs of every post matching a search query string, resolving tag aliases first.
+    pub async fn find_ids_for_query(db: &MySqlPool, query: &str) -> Result<Vec<i32>, ApiError> {
+        let image_query = QueryEngine::parse_query(db, query).await?;
+        QueryEngine::find_ids(db, &image_query).await
+    }
+
     async fn count_images(db: &MySqlPool, image_query: &ImageQuery) -> Result<i32, ApiError> {
         let query_object = QueryEngine::build_query(
             db,
@@ -0,0 +1,231 @@
+use std::collections::HashSet;
+
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use itertools::Itertools;
+use sqlx::{MySqlConnection, MySqlPool};
+
+use super::edit::check_post_editable;
+use super::schema::PostParentSchema;
+use crate::{
+    error::{api_error, api_success, ApiError, ApiErrorType},
+    modules::{
+        posts::model::{PostInfoResponse, PostModel},
+        users::middleware::{get_user, AuthFactory},
+    },
+    AppState,
+};
+
+/// Returns the (siblings, children) of the given post.
+pub async fn fetch_relatives(
+    db: &MySqlPool,
+    post: &PostModel,
+) -> Result<(Vec<i32>, Vec<i32>), ApiError> {
+    let siblings = match post.parent_id {
+        Some(parent_id) => sqlx::query_as::<_, (i32,)>(
+            "SELECT id FROM images WHERE parent_id = ? AND id != ? ORDER BY id ASC",
+        )
+        .bind(parent_id)
+        .bind(post.id)
+        .fetch_all(db)
+        .await?
+        .iter()
+        .map(|(id,)| *id)
+        .collect_vec(),
+        None => Vec::new(),
+    };
+
+    let children = match post.has_children {
+        0 => Vec::new(),
+        _ => {
+            sqlx::query_as::<_, (i32,)>("SELECT id FROM images WHERE parent_id = ? ORDER BY id ASC")
+                .bind(post.id)
+                .fetch_all(db)
+                .await?
+                .iter()
+                .map(|(id,)| *id)
+                .collect_vec()
+        }
+    };
+
+    Ok((siblings, children))
+}
+
+/// Recalculates the cached has_children column for a post.
+pub async fn refresh_has_children(
+    conn: &mut MySqlConnection,
+    post_id: i32,
+) -> Result<(), ApiError> {
+    // MySQL won't let us select from images in a subquery while updating it, so count first
+    let (count,) = sqlx::query_as::<_, (i64,)>("SELECT COUNT(*) FROM images WHERE parent_id = ?")
+        .bind(post_id)
+        .fetch_one(&mut *conn)
+        .await?;
+
+    sqlx::query("UPDATE images SET has_children = ? WHERE id = ?")
+        .bind(count > 0)
+        .bind(post_id)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Makes sure setting `parent_id` as the parent of `post_id` won't create a loop.
+async fn check_cycle(
+    conn: &mut MySqlConnection,
+    post_id: i32,
+    parent_id: i32,
+) -> Result<(), ApiError> {
+    let mut visited: HashSet<i32> = HashSet::new();
+    let mut current = Some(parent_id);
+
+    while let Some(id) = current {
+        if id == post_id {
+            return Err(api_error(
+                ApiErrorType::InvalidRequest,
+                "A post can't be the parent of one of its own ancestors",
+            ));
+        }
+
+        // existing data already has a loop somewhere above us, nothing more to check
+        if !visited.insert(id) {
+            break;
+        }
+
+        current = sqlx::query_as::<_, (Option<i32>,)>("SELECT parent_id FROM images WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&mut *conn)
+            .await?
+            .and_then(|(parent,)| parent);
+    }
+
+    Ok(())
+}
+
+/// Sets or clears the parent of a post, keeping has_children up to date on both the old and new parent.
+pub async fn set_post_parent(
+    conn: &mut MySqlConnection,
+    post_id: i32,
+    parent_id: Option<i32>,
+) -> Result<(), ApiError> {
+    let (previous_parent,) =
+        sqlx::query_as::<_, (Option<i32>,)>("SELECT parent_id FROM images WHERE id = ?")
+            .bind(post_id)
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::RowNotFound => {
+                    api_error(ApiErrorType::InvalidRequest, "Post not found")
+                }
+                _ => e.into(),
+            })?;
+
+    if let Some(parent_id) = parent_id {
+        if parent_id == post_id {
+            return Err(api_error(
+                ApiErrorType::InvalidRequest,
+                "A post can't be its own parent",
+            ));
+        }
+
+        let (count,) = sqlx::query_as::<_, (i64,)>("SELECT COUNT(*) FROM images WHERE id = ?")
+            .bind(parent_id)
+            .fetch_one(&mut *conn)
+            .await?;
+
+        if count < 1 {
+            return Err(api_error(
+                ApiErrorType::InvalidRequest,
+                "Parent post not found",
+            ));
+        }
+
+        check_cycle(conn, post_id, parent_id).await?;
+    }
+
+    sqlx::query("UPDATE images SET parent_id = ? WHERE id = ?")
+        .bind(parent_id)
+        .bind(post_id)
+        .execute(&mut *conn)
+        .await?;
+
+    if let Some(parent_id) = parent_id {
+        sqlx::query("UPDATE images SET has_children = 1 WHERE id = ?")
+            .bind(parent_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    if let Some(previous_parent) = previous_parent {
+        if Some(previous_parent) != parent_id {
+            refresh_has_children(conn, previous_parent).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Detaches a post from its parent and children, in preparation for deleting it.
+pub async fn detach_post(conn: &mut MySqlConnection, post: &PostModel) -> Result<(), ApiError> {
+    sqlx::query("UPDATE images SET parent_id = NULL WHERE parent_id = ?")
+        .bind(post.id)
+        .execute(&mut *conn)
+        .await?;
+
+    if let Some(parent_id) = post.parent_id {
+        sqlx::query("UPDATE images SET parent_id = NULL WHERE id = ?")
+            .bind(post.id)
+            .execute(&mut *conn)
+            .await?;
+
+        refresh_has_children(conn, parent_id).await?;
+    }
+
+    Ok(())
+}
+
+/// Looks up the posts a parent change touches, so their lock state can be checked.
+async fn fetch_post(db: &MySqlPool, post_id: i32) -> Result<Option<PostModel>, ApiError> {
+    Ok(
+        sqlx::query_as!(PostModel, "SELECT * FROM images WHERE id = ?", post_id)
+            .fetch_optional(db)
+            .await?,
+    )
+}
+
+#[post("/parent", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn post_parent_handler(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    body: web::Json<PostParentSchema>,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+
+    let post = fetch_post(&data.db, body.post_id)
+        .await?
+        .ok_or(api_error(ApiErrorType::InvalidRequest, "Post not found"))?;
+
+    // changing the parent also changes has_children on the old and new parent
+    let mut affected = vec![post.parent_id, body.parent_id];
+    affected.dedup();
+    check_post_editable(&data.db, &user, &post).await?;
+    for parent_id in affected.into_iter().flatten() {
+        if let Some(parent) = fetch_post(&data.db, parent_id).await? {
+            check_post_editable(&data.db, &user, &parent).await?;
+        }
+    }
+
+    let mut transaction = data.db.begin().await?;
+    set_post_parent(&mut transaction, body.post_id, body.parent_id).await?;
+    transaction.commit().await?;
+
+    let post = sqlx::query_as!(PostModel, "SELECT * FROM images WHERE id = ?", body.post_id)
+        .fetch_one(&data.db)
+        .await?;
+
+    let mut response = PostInfoResponse::from_model_query(post, &data.db).await?;
+    response.post.sign_media_urls(&*data.storage)?;
+
+    Ok(api_success(response))
+}
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use sqlx::{Acquire, Executor, MySql};
+
+use crate::{
+    error::ApiError,
+    storage::{AppStorage, Rendition, RenditionFormat},
+};
+
+/// How the frames of a storyboard sprite sheet are laid out.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct StoryboardLayout {
+    /// Number of frames in the sheet, read left to right and top to bottom
+    pub frames: u32,
+    pub columns: u32,
+    /// Time between frames, in milliseconds
+    pub interval: u32,
+}
+
+/// A downscaled copy of a post, as listed in post responses.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PostRendition {
+    pub rendition: Rendition,
+    pub format: RenditionFormat,
+    pub mime: String,
+    pub width: i32,
+    pub height: i32,
+    pub filesize: i32,
+    /// Only set for storyboards, where width and height are the size of a single frame
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storyboard: Option<StoryboardLayout>,
+    /// Short-lived signed URL, see [PostRendition::sign_url]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+impl PostRendition {
+    pub fn new(
+        rendition: Rendition,
+        format: RenditionFormat,
+        width: i32,
+        height: i32,
+        filesize: i32,
+    ) -> PostRendition {
+        PostRendition {
+            rendition,
+            format,
+            mime: format.mime().to_owned(),
+            width,
+            height,
+            filesize,
+            storyboard: None,
+            url: None,
+        }
+    }
+
+    pub fn sign_url(&mut self, storage: &AppStorage, hash: String) -> Result<(), ApiError> {
+        self.url = Some(storage.signed_rendition_url(hash, self.rendition, self.format)?);
+        Ok(())
+    }
+}
+
+type RenditionRow = (
+    i32,
+    String,
+    String,
+    i32,
+    i32,
+    i32,
+    Option<u32>,
+    Option<u32>,
+    Option<u32>,
+);
+
+/// Looks up the renditions of several posts at once, smallest first.
+pub async fn fetch_renditions<'a, E: Executor<'a, Database = MySql>>(
+    db: E,
+    post_ids: &[i32],
+) -> Result<HashMap<i32, Vec<PostRendition>>, ApiError> {
+    let mut renditions: HashMap<i32, Vec<PostRendition>> = HashMap::new();
+    if post_ids.is_empty() {
+        return Ok(renditions);
+    }
+
+    let query = format!(
+        "SELECT image_id, rendition, format, width, height, filesize, storyboard_frames, storyboard_columns, storyboard_interval FROM image_renditions WHERE image_id IN ({})",
+        post_ids.iter().map(|id| id.to_string()).join(",")
+    );
+    let rows = sqlx::query_as::<_, RenditionRow>(query.as_str())
+        .fetch_all(db)
+        .await?;
+
+    for (post_id, rendition, format, width, height, filesize, frames, columns, interval) in rows {
+        // skip anything written by a newer version we don't understand
+        let (Some(rendition), Some(format)) = (
+            Rendition::parse(&rendition),
+            RenditionFormat::parse(&format),
+        ) else {
+            continue;
+        };
+
+        let mut post_rendition = PostRendition::new(rendition, format, width, height, filesize);
+        if let (Some(frames), Some(columns), Some(interval)) = (frames, columns, interval) {
+            post_rendition.storyboard = Some(StoryboardLayout {
+                frames,
+                columns,
+                interval,
+            });
+        }
+
+        renditions.entry(post_id).or_default().push(post_rendition);
+    }
+
+    for list in renditions.values_mut() {
+        list.sort_by_key(|r| (r.rendition, r.format));
+    }
+
+    Ok(renditions)
+}
+
+/// Replaces the recorded renditions of a post.
+pub async fn save_renditions<'a, A>(
+    db: A,
+    post_id: i32,
+    renditions: &[PostRendition],
+) -> Result<(), ApiError>
+where
+    A: Acquire<'a, Database = MySql>,
+{
+    let mut conn = db.acquire().await?;
+
+    sqlx::query("DELETE FROM image_renditions WHERE image_id = ?")
+        .bind(post_id)
+        .execute(&mut *conn)
+        .await?;
+
+    for r in renditions {
+        sqlx::query("INSERT INTO image_renditions (image_id, rendition, format, width, height, filesize, storyboard_frames, storyboard_columns, storyboard_interval) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)")
+            .bind(post_id)
+            .bind(r.rendition.name())
+            .bind(r.format.ext())
+            .bind(r.width)
+            .bind(r.height)
+            .bind(r.filesize)
+            .bind(r.storyboard.map(|s| s.frames))
+            .bind(r.storyboard.map(|s| s.columns))
+            .bind(r.storyboard.map(|s| s.interval))
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    Ok(())
+}
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use super::model::PostRating;
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct PostVoteSchema {
     pub post_id: String,
@@ -9,7 +11,21 @@ pub struct PostVoteSchema {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct PostEditSchema {
     pub post_id: String,
-    pub tags: Vec<String>,
+    // each field is only changed if present
+    pub tags: Option<Vec<String>>,
+    // tags to add or remove, applied after `tags` if both are given
+    #[serde(default)]
+    pub add: Vec<String>,
+    #[serde(default)]
+    pub remove: Vec<String>,
+    // the tags the client expects the post to have, the edit is rejected if they've changed
+    pub expected_tags: Option<Vec<String>>,
+    // the tags_version the client last saw, the edit is rejected if it's changed
+    pub tags_version: Option<i32>,
+    // an empty string clears the source
+    pub source: Option<String>,
+    pub rating: Option<PostRating>,
+    pub locked: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -26,3 +42,47 @@ pub struct PostInfoSchema {
 pub struct PostViewSchema {
     pub id: String,
 }
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PostParentSchema {
+    pub post_id: i32,
+    // null or missing to clear the parent
+    pub parent_id: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PostHistorySchema {
+    pub post_id: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PostUserHistorySchema {
+    pub user_id: i32,
+    pub limit: Option<i32>,
+    pub offset: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PostRevertSchema {
+    pub post_id: i32,
+    // the history entry whose tags the post should be reverted to
+    pub history_id: i32,
+    // the tags_version the client last saw, the revert is rejected if it's changed
+    pub tags_version: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PostMassEditSchema {
+    /// A search query selecting the posts to edit
+    pub query: String,
+    #[serde(default)]
+    pub add: Vec<String>,
+    #[serde(default)]
+    pub remove: Vec<String>,
+    /// Pairs of [from, to], replacing the first tag with the second wherever it's present
+    #[serde(default)]
+    pub replace: Vec<(String, String)>,
+    /// Only report which posts would change, without editing anything
+    #[serde(default)]
+    pub dry_run: bool,
+}
@@ -0,0 +1,202 @@
+use itertools::Itertools;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use reqwest::Url;
+use sqlx::MySqlPool;
+
+use crate::error::ApiError;
+
+/// Hosts that serve the same posts as another host, mapped to the host we store.
+static MIRROR_HOSTS: &[(&str, &str)] = &[
+    ("e926.net", "e621.net"),
+    ("x.com", "twitter.com"),
+    ("mobile.twitter.com", "twitter.com"),
+    ("mobile.x.com", "twitter.com"),
+    ("fxtwitter.com", "twitter.com"),
+    ("vxtwitter.com", "twitter.com"),
+    ("fixupx.com", "twitter.com"),
+    ("old.reddit.com", "reddit.com"),
+    ("new.reddit.com", "reddit.com"),
+    ("m.furaffinity.net", "furaffinity.net"),
+];
+
+// old-style e621 post links, like /post/show/12345/some-tags
+static E621_LEGACY_POST_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^/post/show/(\d+)").unwrap());
+
+static E621_POST_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^/posts/(\d+)$").unwrap());
+
+fn canonical_host(host: &str) -> String {
+    let host = host.strip_prefix("www.").unwrap_or(host);
+
+    MIRROR_HOSTS
+        .iter()
+        .find(|(mirror, _)| *mirror == host)
+        .map(|(_, canonical)| (*canonical).to_owned())
+        .unwrap_or(host.to_owned())
+}
+
+/// Normalizes a source so the same page is always stored the same way.
+/// Sources that aren't http(s) URLs are only trimmed.
+pub fn normalize_source(source: &str) -> Option<String> {
+    let source = source.trim();
+    if source.is_empty() {
+        return None;
+    }
+
+    let with_scheme = match source.contains("://") {
+        true => source.to_owned(),
+        // only treat scheme-less text as a URL if it looks like it starts with a domain
+        false => match source.split('/').next().unwrap_or("").contains('.') {
+            true => format!("https://{}", source),
+            false => return Some(source.to_owned()),
+        },
+    };
+
+    let url = match Url::parse(&with_scheme) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => url,
+        _ => return Some(source.to_owned()),
+    };
+
+    let host = match url.host_str() {
+        Some(host) => canonical_host(host),
+        None => return Some(source.to_owned()),
+    };
+
+    let mut path = url.path().trim_end_matches('/').to_owned();
+    let mut query = url.query().filter(|q| !q.is_empty()).map(|q| q.to_owned());
+
+    match host.as_str() {
+        "e621.net" => {
+            let post_id = E621_LEGACY_POST_REGEX
+                .captures(&path)
+                .or(E621_POST_REGEX.captures(&path))
+                .map(|c| c[1].to_owned());
+
+            // post links often carry the search they were opened from
+            if let Some(post_id) = post_id {
+                path = format!("/posts/{}", post_id);
+                query = None;
+            }
+        }
+        // tracking parameters only
+        "twitter.com" => query = None,
+        _ => {}
+    }
+
+    let mut normalized = format!("https://{}", host);
+    if let Some(port) = url.port() {
+        normalized.push_str(format!(":{}", port).as_str());
+    }
+    normalized.push_str(path.as_str());
+    if let Some(query) = query {
+        normalized.push('?');
+        normalized.push_str(query.as_str());
+    }
+
+    Some(normalized)
+}
+
+/// Finds the IDs of every post whose source matches the given source once normalized.
+pub async fn find_posts_by_source(db: &MySqlPool, source: &str) -> Result<Vec<i32>, ApiError> {
+    let normalized = match normalize_source(source) {
+        Some(normalized) => normalized,
+        None => return Ok(Vec::new()),
+    };
+
+    let result = sqlx::query_as::<_, (i32,)>("SELECT id FROM images WHERE source = ?")
+        .bind(normalized)
+        .fetch_all(db)
+        .await?;
+
+    Ok(result.iter().map(|(id,)| *id).collect_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_normalized(source: &str, expected: &str) {
+        assert_eq!(normalize_source(source).as_deref(), Some(expected));
+    }
+
+    #[test]
+    fn mirror_hosts() {
+        assert_normalized(
+            "https://e926.net/posts/12345",
+            "https://e621.net/posts/12345",
+        );
+        assert_normalized(
+            "https://x.com/artist/status/1234",
+            "https://twitter.com/artist/status/1234",
+        );
+        assert_normalized(
+            "https://mobile.twitter.com/artist/status/1234",
+            "https://twitter.com/artist/status/1234",
+        );
+        assert_normalized(
+            "https://www.furaffinity.net/view/1234",
+            "https://furaffinity.net/view/1234",
+        );
+        assert_normalized(
+            "https://old.reddit.com/r/art/comments/abc",
+            "https://reddit.com/r/art/comments/abc",
+        );
+    }
+
+    #[test]
+    fn http_becomes_https() {
+        assert_normalized("http://example.com/art.png", "https://example.com/art.png");
+        // scheme-less sources that start with a domain are URLs too
+        assert_normalized("example.com/art.png", "https://example.com/art.png");
+        assert_normalized(
+            "http://example.com:8080/art.png",
+            "https://example.com:8080/art.png",
+        );
+    }
+
+    #[test]
+    fn trailing_slashes() {
+        assert_normalized(
+            "https://example.com/gallery/",
+            "https://example.com/gallery",
+        );
+        assert_normalized("https://example.com/", "https://example.com");
+    }
+
+    #[test]
+    fn e621_post_paths() {
+        assert_normalized(
+            "https://e621.net/post/show/12345/some-tags",
+            "https://e621.net/posts/12345",
+        );
+        assert_normalized(
+            "https://e621.net/posts/12345?q=some+tags",
+            "https://e621.net/posts/12345",
+        );
+        // other pages keep their queries
+        assert_normalized(
+            "https://e621.net/pools?search=art",
+            "https://e621.net/pools?search=art",
+        );
+    }
+
+    #[test]
+    fn twitter_query_strings() {
+        assert_normalized(
+            "https://twitter.com/artist/status/1234?s=20&t=abc",
+            "https://twitter.com/artist/status/1234",
+        );
+        assert_normalized(
+            "https://example.com/view?id=1234",
+            "https://example.com/view?id=1234",
+        );
+    }
+
+    #[test]
+    fn other_sources() {
+        assert_normalized("  drawn by a friend  ", "drawn by a friend");
+        assert_normalized("ftp://example.com/art.png", "ftp://example.com/art.png");
+        assert_eq!(normalize_source("   "), None);
+    }
+}
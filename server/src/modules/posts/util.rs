@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use sqlx::MySqlPool;
+use sqlx::{Executor, MySql};
 
 use crate::error::ApiError;
 
@@ -10,7 +10,10 @@ pub struct PostTag {
     pub count: i32,
 }
 
-pub async fn fetch_tags(db: &MySqlPool, tags: &Vec<String>) -> Result<Vec<PostTag>, ApiError> {
+pub async fn fetch_tags<'e, E>(db: E, tags: &Vec<String>) -> Result<Vec<PostTag>, ApiError>
+where
+    E: Executor<'e, Database = MySql>,
+{
     if tags.len() == 0 {
         return Ok(Vec::new());
     }
@@ -0,0 +1,203 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use chrono::{Duration, Utc};
+use log::error;
+use serde::{Deserialize, Serialize};
+use sqlx::MySqlPool;
+
+use crate::{
+    booru_config::BooruConfig,
+    error::{api_error, api_success, ApiError, ApiErrorType},
+    modules::{
+        jobs::{model::JobStartedResponse, start_job, JobHandle},
+        posts::new::regenerate_renditions,
+        users::{
+            middleware::{get_user, AuthFactory},
+            permissions::{require_permission, Permission},
+        },
+    },
+    storage::{AppStorage, Rendition, StoredFile},
+    AppState,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IntegrityCheckSchema {
+    /// Delete files that don't belong to any post
+    #[serde(default)]
+    pub delete_orphans: bool,
+    /// Create thumbnails for posts that are missing them
+    #[serde(default)]
+    pub regenerate_thumbnails: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct IntegrityReport {
+    /// Files in storage that don't belong to any post
+    pub orphaned_files: Vec<String>,
+    /// Posts whose original file is missing
+    pub missing_images: Vec<i32>,
+    /// Posts whose thumbnail is missing
+    pub missing_thumbs: Vec<i32>,
+    pub deleted_orphans: i32,
+    pub regenerated_thumbs: i32,
+    /// Posts whose thumbnails couldn't be regenerated
+    pub failed_thumbs: Vec<i32>,
+}
+
+// files newer than this are left alone, their post might still be being created
+const ORPHAN_GRACE_PERIOD: Duration = Duration::hours(1);
+
+/// Lists every file under a storage directory, along with the hash of the post it belongs to.
+async fn list_files(
+    storage: &AppStorage,
+    prefix: &str,
+) -> Result<Vec<(String, StoredFile)>, ApiError> {
+    Ok(storage
+        .list(prefix.to_owned())
+        .await?
+        .into_iter()
+        .filter_map(|file| {
+            // renditions have the format's extension after the hash
+            let hash = file
+                .path
+                .strip_prefix(prefix)?
+                .split('.')
+                .next()?
+                .to_owned();
+            Some((hash, file))
+        })
+        .collect())
+}
+
+async fn run_integrity_check(
+    job: JobHandle,
+    db: MySqlPool,
+    storage: Arc<AppStorage>,
+    config: BooruConfig,
+    options: IntegrityCheckSchema,
+) -> Result<IntegrityReport, ApiError> {
+    let image_files = list_files(&*storage, "images/").await?;
+    let thumb_files = list_files(&*storage, "thumbs/").await?;
+    let mut rendition_files = Vec::new();
+    for rendition in Rendition::ALL {
+        rendition_files
+            .extend(list_files(&*storage, &format!("renditions/{}/", rendition.name())).await?);
+    }
+
+    let images: HashSet<&String> = image_files.iter().map(|(hash, _)| hash).collect();
+    let thumbs: HashSet<&String> = thumb_files.iter().map(|(hash, _)| hash).collect();
+
+    let posts: HashMap<String, i32> =
+        sqlx::query_as::<_, (i32, String)>("SELECT id, hash FROM images")
+            .fetch_all(&db)
+            .await?
+            .into_iter()
+            .map(|(id, hash)| (hash, id))
+            .collect();
+
+    let mut report = IntegrityReport::default();
+
+    // the post is only inserted after its files are written, so recent files could still get one
+    let cutoff = Utc::now() - ORPHAN_GRACE_PERIOD;
+    for (hash, file) in image_files
+        .iter()
+        .chain(thumb_files.iter())
+        .chain(rendition_files.iter())
+    {
+        if !posts.contains_key(hash) && file.modified.map_or(true, |m| m < cutoff) {
+            report.orphaned_files.push(file.path.clone());
+        }
+    }
+
+    for (hash, id) in &posts {
+        if !images.contains(hash) {
+            report.missing_images.push(*id);
+        }
+        if !thumbs.contains(hash) {
+            report.missing_thumbs.push(*id);
+        }
+    }
+
+    report.orphaned_files.sort();
+    report.missing_images.sort();
+    report.missing_thumbs.sort();
+
+    let mut total = 0;
+    if options.delete_orphans {
+        total += report.orphaned_files.len() as i32;
+    }
+    if options.regenerate_thumbnails {
+        total += report.missing_thumbs.len() as i32;
+    }
+
+    let mut progress = 0;
+    job.set_progress(progress, total).await?;
+
+    if options.delete_orphans {
+        for path in &report.orphaned_files {
+            match storage.delete_file(path.clone()).await {
+                Ok(_) => report.deleted_orphans += 1,
+                Err(e) => error!("Failed to delete orphaned file {}: {:?}", path, e),
+            }
+
+            progress += 1;
+            job.set_progress(progress, total).await?;
+        }
+    }
+
+    if options.regenerate_thumbnails {
+        let hashes: HashMap<i32, &String> = posts.iter().map(|(hash, id)| (*id, hash)).collect();
+
+        for id in &report.missing_thumbs {
+            let hash = hashes[id];
+
+            // nothing to create a thumbnail from
+            if !images.contains(hash) {
+                report.failed_thumbs.push(*id);
+            } else {
+                match regenerate_renditions(&db, &*storage, &config, *id, hash.clone()).await {
+                    Ok(_) => report.regenerated_thumbs += 1,
+                    Err(e) => {
+                        error!("Failed to regenerate thumbnail for post {}: {}", id, e);
+                        report.failed_thumbs.push(*id);
+                    }
+                }
+            }
+
+            progress += 1;
+            job.set_progress(progress, total).await?;
+        }
+    }
+
+    Ok(report)
+}
+
+#[post("/integrity", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn system_integrity_handler(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    body: web::Json<IntegrityCheckSchema>,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+
+    require_permission(&data.db, &user, Permission::ManageSystem).await?;
+
+    let db = data.db.clone();
+    let storage = data.storage.clone();
+    let config = data.booru_config.clone();
+    let options = body.into_inner();
+
+    let job_id = start_job(
+        &data.db,
+        "integrity_check",
+        user.id,
+        &options.clone(),
+        move |job| run_integrity_check(job, db, storage, config, options),
+    )
+    .await?;
+
+    Ok(api_success(JobStartedResponse { job_id }))
+}
@@ -0,0 +1,141 @@
+use std::sync::Arc;
+
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use log::error;
+use serde::{Deserialize, Serialize};
+use sqlx::MySqlPool;
+
+use crate::{
+    error::{api_error, api_success, ApiError, ApiErrorType},
+    modules::{
+        jobs::{model::JobStartedResponse, start_job, JobHandle},
+        posts::new::probe_original,
+        users::{
+            middleware::{get_user, AuthFactory},
+            permissions::{require_permission, Permission},
+        },
+    },
+    storage::AppStorage,
+    AppState,
+};
+
+// how many posts to process between checkpoints
+const CHECKPOINT_INTERVAL: usize = 20;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct MediaInfoBackfillSchema {
+    /// Probe every post again, not just the ones uploaded before media info was recorded
+    #[serde(default)]
+    pub all: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct MediaInfoBackfillResult {
+    pub updated: i32,
+    /// Posts whose content couldn't be probed
+    pub failed: Vec<i32>,
+}
+
+async fn update_media_info(
+    db: &MySqlPool,
+    storage: &AppStorage,
+    post_id: i32,
+    hash: String,
+) -> Result<(), String> {
+    let info = probe_original(storage, hash).await?;
+
+    // the file type too, which older posts may have been given before the format was recognized
+    sqlx::query("UPDATE images SET video = ?, image = ?, mime = ?, ext = ?, audio = ?, lossless = ?, video_codec = ?, frame_rate = ?, bit_depth = ? WHERE id = ?")
+        .bind(info.is_video())
+        .bind(info.is_image())
+        .bind(info.mime.clone())
+        .bind(info.get_ext())
+        .bind(info.has_audio)
+        .bind(info.lossless)
+        .bind(info.video_codec)
+        .bind(info.frame_rate)
+        .bind(info.bit_depth)
+        .bind(post_id)
+        .execute(db)
+        .await
+        .map_err(|e| {
+            error!("DB error when updating media info: {:?}", e);
+            "Unknown DB error".to_owned()
+        })?;
+
+    Ok(())
+}
+
+pub async fn run_media_info_backfill(
+    job: JobHandle,
+    db: MySqlPool,
+    storage: Arc<AppStorage>,
+    options: MediaInfoBackfillSchema,
+) -> Result<MediaInfoBackfillResult, ApiError> {
+    // posts uploaded before media info was recorded never had their audio column set
+    let query = match options.all {
+        true => "SELECT id, hash FROM images ORDER BY id ASC",
+        false => "SELECT id, hash FROM images WHERE audio IS NULL ORDER BY id ASC",
+    };
+    let posts = sqlx::query_as::<_, (i32, String)>(query)
+        .fetch_all(&db)
+        .await?;
+
+    let total = posts.len() as i32;
+    let cursor = job.cursor.unwrap_or(0);
+    let mut progress = posts.iter().filter(|(id, _)| *id as i64 <= cursor).count() as i32;
+    let mut result: MediaInfoBackfillResult = job.saved_result().unwrap_or_default();
+
+    job.set_progress(progress, total).await?;
+
+    let remaining: Vec<(i32, String)> = posts
+        .into_iter()
+        .filter(|(id, _)| *id as i64 > cursor)
+        .collect();
+
+    for chunk in remaining.chunks(CHECKPOINT_INTERVAL) {
+        for (post_id, hash) in chunk {
+            match update_media_info(&db, &*storage, *post_id, hash.clone()).await {
+                Ok(_) => result.updated += 1,
+                Err(e) => {
+                    error!("Failed to update media info for post {}: {}", post_id, e);
+                    result.failed.push(*post_id);
+                }
+            }
+        }
+
+        progress += chunk.len() as i32;
+        let last_id = chunk.last().map(|(id, _)| *id).unwrap_or(0);
+        job.checkpoint(progress, total, last_id as i64, &result)
+            .await?;
+    }
+
+    Ok(result)
+}
+
+#[post("/media_info", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn system_media_info_handler(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    body: web::Json<MediaInfoBackfillSchema>,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+
+    require_permission(&data.db, &user, Permission::ManageSystem).await?;
+
+    let db = data.db.clone();
+    let storage = data.storage.clone();
+    let options = body.into_inner();
+
+    let job_id = start_job(
+        &data.db,
+        "backfill_media_info",
+        user.id,
+        &options.clone(),
+        move |job| run_media_info_backfill(job, db, storage, options),
+    )
+    .await?;
+
+    Ok(api_success(JobStartedResponse { job_id }))
+}
@@ -1,7 +1,16 @@
 use actix_web::{web, Scope};
 
 mod api;
+mod integrity;
+pub mod media_info;
+pub mod sources;
+pub mod thumbnails;
 
 pub fn scope() -> Scope {
-    web::scope("/system").service(api::system_info_handler)
+    web::scope("/system")
+        .service(api::system_info_handler)
+        .service(integrity::system_integrity_handler)
+        .service(thumbnails::system_thumbnails_handler)
+        .service(media_info::system_media_info_handler)
+        .service(sources::system_sources_handler)
 }
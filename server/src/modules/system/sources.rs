@@ -0,0 +1,91 @@
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use sqlx::MySqlPool;
+
+use crate::{
+    error::{api_error, api_success, ApiError, ApiErrorType},
+    modules::{
+        jobs::{model::JobStartedResponse, start_job, JobHandle},
+        posts::source::normalize_source,
+        users::{
+            middleware::{get_user, AuthFactory},
+            permissions::{require_permission, Permission},
+        },
+    },
+    AppState,
+};
+
+// how many posts to process between checkpoints, sources are cheap to normalize
+const CHECKPOINT_INTERVAL: usize = 500;
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct SourceNormalizationResult {
+    pub updated: i32,
+}
+
+/// Normalizes the sources of posts created before sources were normalized on the way in.
+pub async fn run_source_normalization(
+    job: JobHandle,
+    db: MySqlPool,
+) -> Result<SourceNormalizationResult, ApiError> {
+    let posts = sqlx::query_as::<_, (i32, String)>(
+        "SELECT id, source FROM images WHERE source IS NOT NULL ORDER BY id ASC",
+    )
+    .fetch_all(&db)
+    .await?;
+
+    let total = posts.len() as i32;
+    let cursor = job.cursor.unwrap_or(0);
+    let mut progress = posts.iter().filter(|(id, _)| *id as i64 <= cursor).count() as i32;
+    let mut result: SourceNormalizationResult = job.saved_result().unwrap_or_default();
+
+    job.set_progress(progress, total).await?;
+
+    let remaining: Vec<(i32, String)> = posts
+        .into_iter()
+        .filter(|(id, _)| *id as i64 > cursor)
+        .collect();
+
+    for chunk in remaining.chunks(CHECKPOINT_INTERVAL) {
+        for (post_id, source) in chunk {
+            let normalized = normalize_source(source);
+            if normalized.as_ref() == Some(source) {
+                continue;
+            }
+
+            sqlx::query("UPDATE images SET source = ? WHERE id = ?")
+                .bind(normalized)
+                .bind(post_id)
+                .execute(&db)
+                .await?;
+            result.updated += 1;
+        }
+
+        progress += chunk.len() as i32;
+        let last_id = chunk.last().map(|(id, _)| *id).unwrap_or(0);
+        job.checkpoint(progress, total, last_id as i64, &result)
+            .await?;
+    }
+
+    Ok(result)
+}
+
+#[post("/sources", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn system_sources_handler(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+
+    require_permission(&data.db, &user, Permission::ManageSystem).await?;
+
+    let db = data.db.clone();
+
+    let job_id = start_job(&data.db, "normalize_sources", user.id, &(), move |job| {
+        run_source_normalization(job, db)
+    })
+    .await?;
+
+    Ok(api_success(JobStartedResponse { job_id }))
+}
@@ -0,0 +1,126 @@
+use std::sync::Arc;
+
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use log::error;
+use serde::{Deserialize, Serialize};
+use sqlx::MySqlPool;
+
+use crate::{
+    booru_config::BooruConfig,
+    error::{api_error, api_success, ApiError, ApiErrorType},
+    modules::{
+        jobs::{model::JobStartedResponse, start_job, JobHandle},
+        posts::{new::regenerate_renditions, query::query_engine::QueryEngine},
+        users::{
+            middleware::{get_user, AuthFactory},
+            permissions::{require_permission, Permission},
+        },
+    },
+    storage::AppStorage,
+    AppState,
+};
+
+// how many posts to process between checkpoints
+const CHECKPOINT_INTERVAL: usize = 20;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ThumbnailRegenerationSchema {
+    /// Only regenerate thumbnails of posts matching this search query, or every post if missing
+    pub query: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ThumbnailRegenerationResult {
+    pub regenerated: i32,
+    /// Posts whose thumbnails couldn't be regenerated
+    pub failed: Vec<i32>,
+}
+
+pub async fn run_thumbnail_regeneration(
+    job: JobHandle,
+    db: MySqlPool,
+    storage: Arc<AppStorage>,
+    config: BooruConfig,
+    options: ThumbnailRegenerationSchema,
+) -> Result<ThumbnailRegenerationResult, ApiError> {
+    let mut post_ids = match &options.query {
+        Some(query) => QueryEngine::find_ids_for_query(&db, query).await?,
+        None => sqlx::query_as::<_, (i32,)>("SELECT id FROM images")
+            .fetch_all(&db)
+            .await?
+            .iter()
+            .map(|(id,)| *id)
+            .collect(),
+    };
+    // posts are processed in ID order, so the cursor is the last post we finished
+    post_ids.sort();
+
+    let total = post_ids.len() as i32;
+    let cursor = job.cursor.unwrap_or(0);
+    let mut progress = post_ids.iter().filter(|id| **id as i64 <= cursor).count() as i32;
+    let mut result: ThumbnailRegenerationResult = job.saved_result().unwrap_or_default();
+
+    job.set_progress(progress, total).await?;
+
+    let remaining: Vec<i32> = post_ids
+        .into_iter()
+        .filter(|id| *id as i64 > cursor)
+        .collect();
+
+    for chunk in remaining.chunks(CHECKPOINT_INTERVAL) {
+        for post_id in chunk {
+            let hash = sqlx::query_as::<_, (String,)>("SELECT hash FROM images WHERE id = ?")
+                .bind(post_id)
+                .fetch_optional(&db)
+                .await?;
+
+            // deleted since the job started
+            let Some((hash,)) = hash else {
+                continue;
+            };
+
+            match regenerate_renditions(&db, &*storage, &config, *post_id, hash).await {
+                Ok(_) => result.regenerated += 1,
+                Err(e) => {
+                    error!("Failed to regenerate thumbnail for post {}: {}", post_id, e);
+                    result.failed.push(*post_id);
+                }
+            }
+        }
+
+        progress += chunk.len() as i32;
+        let last_id = chunk.last().copied().unwrap_or(0);
+        job.checkpoint(progress, total, last_id as i64, &result)
+            .await?;
+    }
+
+    Ok(result)
+}
+
+#[post("/thumbnails", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn system_thumbnails_handler(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    body: web::Json<ThumbnailRegenerationSchema>,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+
+    require_permission(&data.db, &user, Permission::ManageSystem).await?;
+
+    let db = data.db.clone();
+    let storage = data.storage.clone();
+    let config = data.booru_config.clone();
+    let options = body.into_inner();
+
+    let job_id = start_job(
+        &data.db,
+        "regenerate_thumbnails",
+        user.id,
+        &options.clone(),
+        move |job| run_thumbnail_regeneration(job, db, storage, config, options),
+    )
+    .await?;
+
+    Ok(api_success(JobStartedResponse { job_id }))
+}
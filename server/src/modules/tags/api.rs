@@ -3,14 +3,18 @@ use std::collections::BTreeMap;
 use crate::modules::{
     posts::query::image_conditions::IMAGE_CONDITIONS, users::middleware::AuthFactory,
 };
-use actix_web::{get, web, HttpResponse};
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
 
 use crate::{
-    error::{api_success, ApiError},
+    error::{api_error, api_success, ApiError, ApiErrorType},
+    modules::users::{
+        middleware::get_user,
+        permissions::{require_permission, Permission},
+    },
     AppState,
 };
 
-use super::model::{TagCategory, TagListResponse};
+use super::model::{TagCategory, TagCorrection, TagListResponse, TagRecountResponse};
 
 #[get("/list", wrap = "AuthFactory { reject_unauthed: true }")]
 pub async fn tags_list_handler(data: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
@@ -33,3 +37,36 @@ pub async fn tags_list_handler(data: web::Data<AppState>) -> Result<HttpResponse
         conditions: IMAGE_CONDITIONS.clone(),
     }))
 }
+
+#[post("/recount", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn tags_recount_handler(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+
+    require_permission(&data.db, &user, Permission::ManageTags).await?;
+
+    let mut transaction = data.db.begin().await?;
+
+    let corrections = sqlx::query_as::<_, TagCorrection>(
+        r#"SELECT t.tag, t.count AS previous_count, CAST(COUNT(it.image_id) AS SIGNED) AS count
+		FROM tags AS t LEFT JOIN image_tags AS it ON it.tag_id = t.id
+		GROUP BY t.id HAVING t.count != COUNT(it.image_id)"#,
+    )
+    .fetch_all(&mut *transaction)
+    .await?;
+
+    sqlx::query(
+        r#"UPDATE tags AS t LEFT JOIN
+		(SELECT tag_id, COUNT(*) AS count FROM image_tags GROUP BY tag_id) AS c ON c.tag_id = t.id
+		SET t.count = COALESCE(c.count, 0)"#,
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(api_success(TagRecountResponse { corrections }))
+}
@@ -4,5 +4,7 @@ mod api;
 mod model;
 
 pub fn scope() -> Scope {
-    web::scope("/tag").service(api::tags_list_handler)
+    web::scope("/tag")
+        .service(api::tags_list_handler)
+        .service(api::tags_recount_handler)
 }
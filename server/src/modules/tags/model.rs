@@ -17,3 +17,16 @@ pub struct TagListResponse {
     pub categories: Vec<TagCategory>,
     pub conditions: Vec<ImageCondition>,
 }
+
+/// A tag whose stored count didn't match the number of posts using it.
+#[derive(Serialize, Deserialize, Debug, sqlx::FromRow)]
+pub struct TagCorrection {
+    pub tag: String,
+    pub previous_count: i32,
+    pub count: i64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TagRecountResponse {
+    pub corrections: Vec<TagCorrection>,
+}
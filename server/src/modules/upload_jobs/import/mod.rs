@@ -16,10 +16,21 @@ fn is_mime_type_supported(mime: &str) -> bool {
     match mime {
         "video/mp4"
         | "video/webm"
+        | "video/x-matroska"
+        | "video/quicktime"
+        | "video/ogg"
+        | "audio/mpeg"
+        | "audio/ogg"
+        | "audio/mp4"
+        | "audio/webm"
+        | "audio/x-matroska"
         | "image/png"
+        | "image/apng"
         | "image/webp"
         | "image/jpeg"
         | "image/gif"
+        | "image/avif"
+        | "image/jxl"
         | "application/x-shockwave-flash" => true,
         _ => false,
     }
@@ -27,7 +38,8 @@ fn is_mime_type_supported(mime: &str) -> bool {
 
 fn is_ext_supported(ext: &str) -> bool {
     match ext {
-        "mp4" | "webm" | "png" | "webp" | "jpg" | "jpeg" | "gif" | "swf" => true,
+        "mp4" | "webm" | "mkv" | "mov" | "ogv" | "mp3" | "ogg" | "m4a" | "weba" | "mka" | "png"
+        | "apng" | "webp" | "jpg" | "jpeg" | "gif" | "avif" | "jxl" | "swf" => true,
         _ => false,
     }
 }
@@ -1,19 +1,41 @@
 use super::model::{
-    filter_db_record, AuthTokenResponse, UserLoginResponse, UserModel, UserRefreshResponse,
+    filter_db_record, AuthTokenResponse, UserLoginResponse, UserModel, UserProfileResponse,
+    UserProfileTag, UserRefreshResponse,
+};
+use super::schema::{
+    UserChangeEmailSchema, UserChangePasswordSchema, UserLoginSchema, UserProfileSchema,
+    UserRefreshSchema, UserRenameSchema, UserSignupSchema,
 };
-use super::schema::{UserLoginSchema, UserRefreshSchema, UserSignupSchema};
 
-use super::middleware::{get_user, AuthFactory};
-use super::util::{check_user, get_basic_auth_header, validate_auth_token, AuthTokenKind};
+use super::api_keys::util::revoke_api_keys;
+use super::bans::check_not_banned;
+use super::middleware::{get_session_id, get_user, AuthFactory};
+use super::permissions::{has_permission, Permission};
+use super::sessions::util::{
+    access_token_expiration, check_session, create_session, revoke_sessions, update_session_client,
+    SessionClient, REMEMBERED_SESSION_LIFETIME,
+};
+use super::util::{
+    check_password, check_user, get_basic_auth_header, validate_auth_token, validate_email,
+    validate_password, validate_username, AuthTokenKind,
+};
 use crate::error::{api_error, api_success, ApiError, ApiErrorType};
+use crate::modules::posts::query::parser::{ContentFilter, ImageQuery};
+use crate::modules::posts::query::query_engine::QueryEngine;
 use crate::modules::users::util::hash_password;
 use crate::AppState;
 use actix_web::{get, post, web, HttpRequest, HttpResponse};
+use chrono::{Duration, Utc};
 use log::error;
 use serde_json::json;
+use sqlx::MySqlPool;
 
 use super::util;
 
+// how many of each are shown on a profile
+const PROFILE_RECENT_UPLOADS: i32 = 6;
+const PROFILE_TOP_TAGS: i32 = 10;
+
 #[get("/info", wrap = "AuthFactory { reject_unauthed: true }")]
 async fn user_info_handler(req: HttpRequest) -> Result<HttpResponse, ApiError> {
     let user = get_user(&req)
@@ -24,6 +46,17 @@ async fn user_info_handler(req: HttpRequest) -> Result<HttpResponse, ApiError> {
 
 #[get("/nginx_callback", wrap = "AuthFactory { reject_unauthed: false }")]
 async fn user_nginx_callback_handler(req: HttpRequest, data: web::Data<AppState>) -> HttpResponse {
+    // signed media URLs carry their own authorization, so they don't need a user lookup
+    let original_uri = req
+        .headers()
+        .get("X-Original-URI")
+        .and_then(|h| h.to_str().ok());
+    if let Some(uri) = original_uri {
+        if data.storage.verify_signed_uri(uri) {
+            return HttpResponse::Ok().json(json!({ "type": "success" }));
+        }
+    }
+
     if let Some(_) = get_user(&req) {
         return HttpResponse::Ok().json(json!({ "type": "success" }));
     }
@@ -44,15 +77,42 @@ async fn user_nginx_callback_handler(req: HttpRequest, data: web::Data<AppState>
 
 #[post("/login")]
 async fn user_login_handler(
+    req: HttpRequest,
     body: web::Json<UserLoginSchema>,
     data: web::Data<AppState>,
 ) -> Result<HttpResponse, ApiError> {
     let user = check_user(&data.db, &body.username, &body.password).await?;
 
-    let access_token: Option<AuthTokenResponse> =
-        util::create_auth_token(&user, util::AuthTokenKind::Access).ok();
-    let refresh_token: Option<AuthTokenResponse> = match body.remember_me.unwrap_or(false) {
-        true => util::create_auth_token(&user, util::AuthTokenKind::Refresh).ok(),
+    let remember_me = body.remember_me.unwrap_or(false);
+    let ttl = data.booru_config.access_token_ttl;
+    let session_expires = Utc::now()
+        + Duration::seconds(match remember_me {
+            true => REMEMBERED_SESSION_LIFETIME,
+            false => ttl,
+        });
+    let session = create_session(
+        &data.db,
+        user.id,
+        &SessionClient::from_request(&req),
+        session_expires,
+    )
+    .await?;
+
+    let access_token: Option<AuthTokenResponse> = util::create_auth_token(
+        &user,
+        &session,
+        util::AuthTokenKind::Access,
+        access_token_expiration(ttl, session_expires),
+    )
+    .ok();
+    let refresh_token: Option<AuthTokenResponse> = match remember_me {
+        true => util::create_auth_token(
+            &user,
+            &session,
+            util::AuthTokenKind::Refresh,
+            session_expires,
+        )
+        .ok(),
         false => None,
     };
 
@@ -75,17 +135,22 @@ async fn user_login_handler(
 
 #[post("/refresh")]
 async fn user_refresh_handler(
+    req: HttpRequest,
     body: web::Json<UserRefreshSchema>,
     data: web::Data<AppState>,
 ) -> Result<HttpResponse, ApiError> {
-    let user_id = validate_auth_token(body.refresh_token.as_str(), AuthTokenKind::Refresh)
-        .map_err(|_e| {
+    let (user_id, token_id) =
+        validate_auth_token(body.refresh_token.as_str(), AuthTokenKind::Refresh).map_err(|_e| {
             api_error(
                 ApiErrorType::OperationFailed,
                 "Failed to validate refresh token",
             )
         })?;
 
+    let (session_id, session_expires) = check_session(&data.db, user_id, &token_id).await?;
+    check_not_banned(&data.db, user_id).await?;
+    update_session_client(&data.db, session_id, &SessionClient::from_request(&req)).await?;
+
     let user = sqlx::query_as!(UserModel, r#"SELECT * FROM users WHERE id = ?"#, user_id)
         .fetch_one(&data.db)
         .await
@@ -97,7 +162,13 @@ async fn user_refresh_handler(
             }
         })?;
 
-    let new_token = util::create_auth_token(&user, AuthTokenKind::Access).map_err(|e| {
+    let new_token = util::create_auth_token(
+        &user,
+        &token_id,
+        AuthTokenKind::Access,
+        access_token_expiration(data.booru_config.access_token_ttl, session_expires),
+    )
+    .map_err(|e| {
         error!("JWT error: {:?}", e);
         api_error(ApiErrorType::ServerError, "Failed to create refresh token")
     })?;
@@ -137,8 +208,11 @@ pub async fn user_signup_handler(
         }
     }
 
+    let name = validate_username(&body.username)?;
+    validate_password(&body.password)?;
+
     let (count,) = sqlx::query_as::<_, (i32,)>("SELECT COUNT(*) FROM users WHERE name = ?")
-        .bind(body.username.as_str())
+        .bind(&name)
         .fetch_one(&data.db)
         .await?;
 
@@ -152,11 +226,12 @@ pub async fn user_signup_handler(
     let hash = hash_password(body.password.as_str())?;
 
     sqlx::query("INSERT INTO users (`name`, `pass`, `email`) VALUES(?, ?, ?)")
-        .bind(body.username.as_str())
+        .bind(&name)
         .bind(hash)
         .bind(body.email.as_ref().and_then(|s| Some(s.clone())))
         .execute(&data.db)
-        .await?;
+        .await
+        .map_err(username_taken_error)?;
 
     if data.booru_config.signup_requires_invite {
         sqlx::query(
@@ -167,15 +242,205 @@ pub async fn user_signup_handler(
         .await?;
     }
 
-    let user = sqlx::query_as!(
-        UserModel,
-        r#"SELECT * FROM users WHERE name = ?"#,
-        body.username
-    )
-    .fetch_one(&data.db)
-    .await?;
+    let user = sqlx::query_as!(UserModel, r#"SELECT * FROM users WHERE name = ?"#, name)
+        .fetch_one(&data.db)
+        .await?;
 
     transaction.commit().await?;
 
     Ok(api_success(filter_db_record(&user)))
 }
+
+/// Turns the error from a name colliding with the unique key into the same one the up front check gives.
+fn username_taken_error(e: sqlx::Error) -> ApiError {
+    match e {
+        sqlx::Error::Database(ref db_error) if db_error.is_unique_violation() => {
+            api_error(ApiErrorType::InvalidRequest, "Username already taken")
+        }
+        _ => e.into(),
+    }
+}
+
+async fn fetch_user(db: &MySqlPool, user_id: i32) -> Result<UserModel, ApiError> {
+    Ok(
+        sqlx::query_as!(UserModel, r#"SELECT * FROM users WHERE id = ?"#, user_id)
+            .fetch_one(db)
+            .await?,
+    )
+}
+
+#[post("/password", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn user_change_password_handler(
+    req: HttpRequest,
+    body: web::Json<UserChangePasswordSchema>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+
+    validate_password(&body.new_password)?;
+    if !check_password(&user, &body.old_password)? {
+        return Err(api_error(
+            ApiErrorType::AuthorizationFailed,
+            "Invalid credentials",
+        ));
+    }
+
+    let hash = hash_password(body.new_password.as_str())?;
+    sqlx::query("UPDATE users SET pass = ? WHERE id = ?")
+        .bind(hash)
+        .bind(user.id)
+        .execute(&data.db)
+        .await?;
+
+    // anyone else who knew the old password shouldn't stay logged in, or keep keys they made with it
+    revoke_sessions(&data.db, user.id, get_session_id(&req)).await?;
+    revoke_api_keys(&data.db, user.id).await?;
+
+    Ok(api_success(filter_db_record(
+        &fetch_user(&data.db, user.id).await?,
+    )))
+}
+
+#[post("/email", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn user_change_email_handler(
+    req: HttpRequest,
+    body: web::Json<UserChangeEmailSchema>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+
+    if !check_password(&user, &body.password)? {
+        return Err(api_error(
+            ApiErrorType::AuthorizationFailed,
+            "Invalid credentials",
+        ));
+    }
+
+    let email = validate_email(body.email.as_ref())?;
+    sqlx::query("UPDATE users SET email = ? WHERE id = ?")
+        .bind(email)
+        .bind(user.id)
+        .execute(&data.db)
+        .await?;
+
+    Ok(api_success(filter_db_record(
+        &fetch_user(&data.db, user.id).await?,
+    )))
+}
+
+#[post("/rename", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn user_rename_handler(
+    req: HttpRequest,
+    body: web::Json<UserRenameSchema>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+
+    let name = validate_username(&body.name)?;
+
+    // names are compared case-insensitively, so changing the case of your own name is fine
+    let (count,) =
+        sqlx::query_as::<_, (i32,)>("SELECT COUNT(*) FROM users WHERE name = ? AND id != ?")
+            .bind(&name)
+            .bind(user.id)
+            .fetch_one(&data.db)
+            .await?;
+
+    if count > 0 {
+        return Err(api_error(
+            ApiErrorType::InvalidRequest,
+            "Username already taken",
+        ));
+    }
+
+    // the unique key on name catches anyone who took it since it was checked
+    sqlx::query("UPDATE users SET name = ? WHERE id = ?")
+        .bind(&name)
+        .bind(user.id)
+        .execute(&data.db)
+        .await
+        .map_err(username_taken_error)?;
+
+    Ok(api_success(filter_db_record(
+        &fetch_user(&data.db, user.id).await?,
+    )))
+}
+
+#[get("/profile", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn user_profile_handler(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    body: web::Query<UserProfileSchema>,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+
+    let profile_user = sqlx::query_as::<_, UserModel>("SELECT * FROM users WHERE name = ?")
+        .bind(body.name.trim())
+        .fetch_optional(&data.db)
+        .await?
+        .ok_or(api_error(ApiErrorType::InvalidRequest, "User not found"))?;
+
+    let show_email = profile_user.id == user.id
+        || has_permission(&data.db, &user, Permission::ManageUsers).await?;
+
+    let (upload_count,) =
+        sqlx::query_as::<_, (i64,)>("SELECT COUNT(*) FROM images WHERE owner_id = ?")
+            .bind(profile_user.id)
+            .fetch_one(&data.db)
+            .await?;
+    let (favorite_count,) =
+        sqlx::query_as::<_, (i64,)>("SELECT COUNT(*) FROM user_favorites WHERE user_id = ?")
+            .bind(profile_user.id)
+            .fetch_one(&data.db)
+            .await?;
+    let (tag_edit_count,) =
+        sqlx::query_as::<_, (i64,)>("SELECT COUNT(*) FROM post_tag_history WHERE user_id = ?")
+            .bind(profile_user.id)
+            .fetch_one(&data.db)
+            .await?;
+
+    let top_tags = sqlx::query_as::<_, (String, i32)>(
+        "SELECT tag, num FROM tag_user_frequencies WHERE user_id = ? AND num > 0 ORDER BY num DESC, tag ASC LIMIT ?",
+    )
+    .bind(profile_user.id)
+    .bind(PROFILE_TOP_TAGS)
+    .fetch_all(&data.db)
+    .await?
+    .into_iter()
+    .map(|(tag, count)| UserProfileTag { tag, count })
+    .collect();
+
+    let filter = ContentFilter {
+        images: true,
+        videos: true,
+        vr: true,
+        ratings: Vec::new(),
+    };
+    let query = ImageQuery::new(
+        vec![format!("poster_id:{}", profile_user.id)],
+        0,
+        PROFILE_RECENT_UPLOADS,
+        filter,
+    )?;
+    let mut recent_uploads = QueryEngine::run(&data.db, query, user.id).await?.posts;
+    for post in recent_uploads.iter_mut() {
+        post.sign_media_urls(&*data.storage)?;
+    }
+
+    Ok(api_success(UserProfileResponse {
+        id: profile_user.id,
+        name: profile_user.name,
+        joindate: profile_user.joindate,
+        class: profile_user.class,
+        email: profile_user.email.filter(|_| show_email),
+        upload_count,
+        favorite_count,
+        tag_edit_count,
+        recent_uploads,
+        top_tags,
+    }))
+}
@@ -0,0 +1,121 @@
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
+use itertools::Itertools;
+use sqlx::MySqlPool;
+
+use crate::{
+    error::{api_error, api_success, ApiError, ApiErrorType},
+    modules::users::middleware::{get_user, AuthFactory},
+    AppState,
+};
+
+use super::model::{
+    ApiKeyModel, ApiKeyResponse, UserCreateApiKeyResponse, UserCreateApiKeySchema,
+    UserDeleteApiKeySchema, UserListApiKeysResponse,
+};
+use super::util::generate_api_key;
+
+// a user shouldn't need more than this many scripts
+const MAX_KEYS_PER_USER: usize = 25;
+
+async fn get_user_api_keys(db: &MySqlPool, user_id: i32) -> Result<Vec<ApiKeyModel>, ApiError> {
+    let models = sqlx::query_as::<_, ApiKeyModel>(
+        "SELECT id, user_id, name, prefix, scopes, created_at, last_used_at FROM user_api_keys WHERE user_id = ? ORDER BY id ASC",
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(models)
+}
+
+#[get("/list", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn user_list_api_keys_handler(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+
+    let keys = get_user_api_keys(&data.db, user.id).await?;
+    Ok(api_success(UserListApiKeysResponse {
+        keys: keys.iter().map(ApiKeyResponse::from).collect(),
+    }))
+}
+
+#[post("/new", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn user_create_api_key_handler(
+    body: web::Json<UserCreateApiKeySchema>,
+    data: web::Data<AppState>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+
+    let name = body.name.trim();
+    if name.is_empty() || name.chars().count() > 64 {
+        return Err(api_error(
+            ApiErrorType::InvalidRequest,
+            "API key names must be between 1 and 64 characters",
+        ));
+    }
+    if body.scopes.is_empty() {
+        return Err(api_error(
+            ApiErrorType::InvalidRequest,
+            "API keys need at least one scope",
+        ));
+    }
+
+    if get_user_api_keys(&data.db, user.id).await?.len() >= MAX_KEYS_PER_USER {
+        return Err(api_error(
+            ApiErrorType::OperationFailed,
+            "Too many API keys, delete an unused one first",
+        ));
+    }
+
+    let (key, prefix, hash) = generate_api_key()?;
+    let scopes = body.scopes.iter().map(|s| s.name()).unique().join(",");
+
+    let result = sqlx::query(
+        "INSERT INTO user_api_keys (user_id, name, prefix, key_hash, scopes) VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind(user.id)
+    .bind(name)
+    .bind(prefix)
+    .bind(hash)
+    .bind(scopes)
+    .execute(&data.db)
+    .await?;
+
+    let api_key = sqlx::query_as::<_, ApiKeyModel>(
+        "SELECT id, user_id, name, prefix, scopes, created_at, last_used_at FROM user_api_keys WHERE id = ?",
+    )
+    .bind(result.last_insert_id())
+    .fetch_one(&data.db)
+    .await?;
+
+    Ok(api_success(UserCreateApiKeyResponse {
+        key,
+        api_key: ApiKeyResponse::from(&api_key),
+    }))
+}
+
+#[post("/delete", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn user_delete_api_key_handler(
+    body: web::Json<UserDeleteApiKeySchema>,
+    data: web::Data<AppState>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+
+    sqlx::query("DELETE FROM user_api_keys WHERE user_id = ? AND id = ?")
+        .bind(user.id)
+        .bind(body.key_id)
+        .execute(&data.db)
+        .await?;
+
+    let keys = get_user_api_keys(&data.db, user.id).await?;
+    Ok(api_success(UserListApiKeysResponse {
+        keys: keys.iter().map(ApiKeyResponse::from).collect(),
+    }))
+}
@@ -0,0 +1,12 @@
+use actix_web::{web, Scope};
+
+pub mod api;
+pub mod model;
+pub mod util;
+
+pub fn scope() -> Scope {
+    web::scope("/api_key")
+        .service(api::user_list_api_keys_handler)
+        .service(api::user_create_api_key_handler)
+        .service(api::user_delete_api_key_handler)
+}
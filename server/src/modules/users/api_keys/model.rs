@@ -0,0 +1,100 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// What a request authenticated with an API key is allowed to do.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiKeyScope {
+    /// Any GET endpoint
+    Read,
+    /// Uploading and importing new posts
+    Upload,
+    /// Editing the tags, parents and history of posts
+    TagEdit,
+}
+
+impl ApiKeyScope {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ApiKeyScope::Read => "read",
+            ApiKeyScope::Upload => "upload",
+            ApiKeyScope::TagEdit => "tag_edit",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<ApiKeyScope> {
+        match name {
+            "read" => Some(ApiKeyScope::Read),
+            "upload" => Some(ApiKeyScope::Upload),
+            "tag_edit" => Some(ApiKeyScope::TagEdit),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct ApiKeyModel {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl ApiKeyModel {
+    pub fn scopes(&self) -> Vec<ApiKeyScope> {
+        self.scopes
+            .split(',')
+            .filter_map(ApiKeyScope::parse)
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiKeyResponse {
+    pub id: i32,
+    pub name: String,
+    /// The start of the key, so it can be told apart from the user's other keys
+    pub prefix: String,
+    pub scopes: Vec<ApiKeyScope>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl From<&ApiKeyModel> for ApiKeyResponse {
+    fn from(model: &ApiKeyModel) -> ApiKeyResponse {
+        ApiKeyResponse {
+            id: model.id,
+            name: model.name.clone(),
+            prefix: model.prefix.clone(),
+            scopes: model.scopes(),
+            created_at: model.created_at,
+            last_used_at: model.last_used_at,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UserListApiKeysResponse {
+    pub keys: Vec<ApiKeyResponse>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UserCreateApiKeySchema {
+    pub name: String,
+    pub scopes: Vec<ApiKeyScope>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UserCreateApiKeyResponse {
+    /// The full key. It's only stored hashed, so this is the only time it can be seen
+    pub key: String,
+    pub api_key: ApiKeyResponse,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UserDeleteApiKeySchema {
+    pub key_id: i32,
+}
@@ -0,0 +1,112 @@
+use actix_web::http::Method;
+use log::error;
+use rand::RngCore;
+use sqlx::MySqlPool;
+
+use crate::{
+    error::{api_error, ApiError, ApiErrorType},
+    modules::users::{model::UserModel, util::hash_token},
+};
+
+use super::model::{ApiKeyModel, ApiKeyScope};
+
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+
+// every key starts with this, so leaked keys are easy to search for
+const KEY_PREFIX: &str = "tk_";
+// how much of the key is kept in plaintext, to tell keys apart in listings
+const DISPLAY_PREFIX_LENGTH: usize = 10;
+
+/// Generates a new key, returning it with its display prefix and hash.
+pub fn generate_api_key() -> Result<(String, String, String), ApiError> {
+    let mut bytes = [0x00; 24];
+    rand::thread_rng().try_fill_bytes(&mut bytes).map_err(|e| {
+        error!("Can't fill bytes from RNG: {:?}", e);
+        api_error(ApiErrorType::ServerError, "Failed to generate API key")
+    })?;
+
+    let key = format!("{}{}", KEY_PREFIX, bs58::encode(bytes).into_string());
+    let prefix = key[..DISPLAY_PREFIX_LENGTH].to_owned();
+    let hash = hash_token(&key);
+    Ok((key, prefix, hash))
+}
+
+/// The scope a key needs to use an endpoint, or None if keys can't use it at all.
+/// `pattern` is the matched route, like `/api/post/edit`.
+pub fn required_scope(method: &Method, pattern: &str) -> Option<ApiKeyScope> {
+    // keys can't be used to manage keys or sessions, even just to list them
+    if pattern.contains("/user/api_key/") || pattern.contains("/user/session/") {
+        return None;
+    }
+
+    if method == Method::GET {
+        return Some(ApiKeyScope::Read);
+    }
+
+    const UPLOAD: [&str; 3] = ["/post/new", "/import/prepare", "/import/resolve"];
+    const TAG_EDIT: [&str; 4] = [
+        "/post/edit",
+        "/post/mass_edit",
+        "/post/parent",
+        "/post/revert",
+    ];
+
+    if UPLOAD.iter().any(|p| pattern.ends_with(p)) {
+        Some(ApiKeyScope::Upload)
+    } else if TAG_EDIT.iter().any(|p| pattern.ends_with(p)) {
+        Some(ApiKeyScope::TagEdit)
+    } else {
+        None
+    }
+}
+
+/// Looks up the owner of a key, checking that it's allowed to be used for `scope`.
+pub async fn check_api_key(
+    db: &MySqlPool,
+    key: &str,
+    scope: Option<ApiKeyScope>,
+) -> Result<UserModel, ApiError> {
+    let api_key = sqlx::query_as::<_, ApiKeyModel>(
+        "SELECT id, user_id, name, prefix, scopes, created_at, last_used_at FROM user_api_keys WHERE key_hash = ?",
+    )
+    .bind(hash_token(key))
+    .fetch_optional(db)
+    .await?
+    .ok_or(api_error(ApiErrorType::AuthorizationFailed, "Invalid API key"))?;
+
+    let Some(scope) = scope else {
+        return Err(api_error(
+            ApiErrorType::Forbidden,
+            "API keys can't be used for this endpoint",
+        ));
+    };
+    if !api_key.scopes().contains(&scope) {
+        return Err(api_error(
+            ApiErrorType::Forbidden,
+            "API key is missing the scope for this endpoint",
+        ));
+    }
+
+    // only written once a minute, so busy scripts don't write on every request
+    sqlx::query("UPDATE user_api_keys SET last_used_at = NOW() WHERE id = ? AND (last_used_at IS NULL OR last_used_at < NOW() - INTERVAL 1 MINUTE)")
+        .bind(api_key.id)
+        .execute(db)
+        .await?;
+
+    let user = sqlx::query_as::<_, UserModel>("SELECT * FROM users WHERE id = ?")
+        .bind(api_key.user_id)
+        .fetch_one(db)
+        .await?;
+
+    Ok(user)
+}
+
+/// Deletes every API key of a user, returning how many there were.
+pub async fn revoke_api_keys(db: &MySqlPool, user_id: i32) -> Result<u64, ApiError> {
+    let result = sqlx::query("DELETE FROM user_api_keys WHERE user_id = ?")
+        .bind(user_id)
+        .execute(db)
+        .await?;
+
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,51 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Executor, MySql};
+
+use crate::error::{api_error_with_data, ApiError, ApiErrorType};
+
+/// Matches bans that haven't ended or been lifted.
+pub const ACTIVE_BAN_CONDITION: &str =
+    "lifted_at IS NULL AND (expires_at IS NULL OR expires_at > NOW())";
+
+#[derive(Serialize, Deserialize, Debug, Clone, sqlx::FromRow)]
+pub struct UserBanModel {
+    pub id: i32,
+    pub user_id: i32,
+    pub banned_by: Option<i32>,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+    /// When the ban ends, or None if it's permanent
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Looks up the ban currently in effect for a user, if there is one.
+pub async fn find_active_ban<'a, E: Executor<'a, Database = MySql>>(
+    db: E,
+    user_id: i32,
+) -> Result<Option<UserBanModel>, ApiError> {
+    let query = format!(
+        "SELECT id, user_id, banned_by, reason, created_at, expires_at FROM user_bans WHERE user_id = ? AND {} ORDER BY id DESC LIMIT 1",
+        ACTIVE_BAN_CONDITION
+    );
+
+    Ok(sqlx::query_as::<_, UserBanModel>(query.as_str())
+        .bind(user_id)
+        .fetch_optional(db)
+        .await?)
+}
+
+/// Fails with a Forbidden error, carrying the ban's reason and expiration, if the user is banned.
+pub async fn check_not_banned<'a, E: Executor<'a, Database = MySql>>(
+    db: E,
+    user_id: i32,
+) -> Result<(), ApiError> {
+    match find_active_ban(db, user_id).await? {
+        Some(ban) => Err(api_error_with_data(
+            ApiErrorType::Forbidden,
+            "This account is banned",
+            ban,
+        )),
+        None => Ok(()),
+    }
+}
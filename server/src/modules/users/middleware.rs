@@ -10,8 +10,15 @@ use super::model::UserModel;
 use crate::error::{api_error, ApiError, ApiErrorType};
 use crate::AppState;
 
+use super::api_keys::util::{check_api_key, required_scope, API_KEY_HEADER};
+use super::bans::check_not_banned;
+use super::sessions::util::check_session;
 use super::util::validate_auth_header;
 
+/// The session a request was authorized with.
+#[derive(Clone, Copy)]
+struct CurrentSession(i32);
+
 pub struct AuthFactory {
     pub reject_unauthed: bool,
 }
@@ -42,7 +49,8 @@ pub struct AuthMiddleware<S> {
 }
 
 impl<S> AuthMiddleware<S> {
-    async fn handle_call(req: &mut ServiceRequest) -> Result<UserModel, ApiError> {
+    /// Returns the authorized user, and the session they logged in with if they didn't use an API key.
+    async fn handle_call(req: &mut ServiceRequest) -> Result<(UserModel, Option<i32>), ApiError> {
         let state = req
             .extract::<web::Data<AppState>>()
             .await
@@ -50,15 +58,32 @@ impl<S> AuthMiddleware<S> {
 
         let pool = &state.db;
 
+        let api_key = req
+            .headers()
+            .get(API_KEY_HEADER)
+            .and_then(|h| h.to_str().ok())
+            .map(|k| k.to_owned());
+        if let Some(key) = api_key {
+            let scope = required_scope(req.method(), req.match_pattern().as_deref().unwrap_or(""));
+            let user = check_api_key(pool, &key, scope).await?;
+            check_not_banned(pool, user.id).await?;
+            return Ok((user, None));
+        }
+
         match validate_auth_header(req, super::util::AuthTokenKind::Access) {
-            Ok(user_id) => {
+            Ok((user_id, token_id)) => {
+                let (session_id, _) = check_session(pool, user_id, &token_id).await?;
+
                 let query_result =
                     sqlx::query_as!(UserModel, r#"SELECT * FROM users WHERE id = ?"#, user_id)
                         .fetch_one(pool)
                         .await;
 
                 match query_result {
-                    Ok(user) => Ok(user),
+                    Ok(user) => {
+                        check_not_banned(pool, user.id).await?;
+                        Ok((user, Some(session_id)))
+                    }
                     Err(sqlx::Error::RowNotFound) => Err(api_error(
                         ApiErrorType::AuthorizationFailed,
                         "User not found",
@@ -95,9 +120,13 @@ where
 
         Box::pin(async move {
             let result = AuthMiddleware::<S>::handle_call(&mut req).await;
-            if let Ok(user) = &result {
+            if let Ok((user, session_id)) = &result {
                 req.extensions_mut()
                     .insert::<Rc<UserModel>>(Rc::new(user.clone()));
+                if let Some(session_id) = session_id {
+                    req.extensions_mut()
+                        .insert::<CurrentSession>(CurrentSession(*session_id));
+                }
             }
 
             if reject_unauthed && result.is_err() {
@@ -115,3 +144,10 @@ pub fn get_user(req: &HttpRequest) -> Option<Rc<UserModel>> {
         None => None,
     }
 }
+
+/// The ID of the session the request was authorized with, or None if an API key was used.
+pub fn get_session_id(req: &HttpRequest) -> Option<i32> {
+    req.extensions()
+        .get::<CurrentSession>()
+        .map(|session| session.0)
+}
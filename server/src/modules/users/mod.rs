@@ -1,11 +1,16 @@
 use actix_web::{web, Scope};
 
 mod api;
+pub mod api_keys;
+pub mod bans;
 mod config;
 mod invites;
 pub mod middleware;
 pub mod model;
+mod password_reset;
+pub mod permissions;
 mod schema;
+pub mod sessions;
 pub mod user;
 pub mod util;
 
@@ -13,10 +18,17 @@ pub fn scope() -> Scope {
     web::scope("/user")
         .service(api::user_login_handler)
         .service(api::user_info_handler)
+        .service(api::user_profile_handler)
         .service(api::user_refresh_handler)
         .service(api::user_nginx_callback_handler)
         .service(api::user_signup_handler)
+        .service(api::user_change_password_handler)
+        .service(api::user_change_email_handler)
+        .service(api::user_rename_handler)
         .service(config::api::user_config_get_handler)
         .service(config::api::user_config_set_handler)
         .service(invites::scope())
+        .service(api_keys::scope())
+        .service(sessions::scope())
+        .service(password_reset::scope())
 }
@@ -1,6 +1,8 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::modules::posts::query::model::PostQueryResult;
+
 #[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
 #[allow(non_snake_case)]
 pub struct UserModel {
@@ -51,3 +53,26 @@ pub struct UserRefreshResponse {
     pub access: AuthTokenResponse,
     pub user: UserModelResponse,
 }
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UserProfileTag {
+    pub tag: String,
+    /// How many times the user has added the tag
+    pub count: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UserProfileResponse {
+    pub id: i32,
+    pub name: String,
+    pub joindate: DateTime<Utc>,
+    pub class: String,
+    /// Only shown to the user themselves and to users who can manage users
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    pub upload_count: i64,
+    pub favorite_count: i64,
+    pub tag_edit_count: i64,
+    pub recent_uploads: Vec<PostQueryResult>,
+    pub top_tags: Vec<UserProfileTag>,
+}
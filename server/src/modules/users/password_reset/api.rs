@@ -0,0 +1,344 @@
+use std::sync::Arc;
+
+use actix_web::{post, web, HttpResponse};
+use chrono::{Duration, Utc};
+use log::error;
+use rand::RngCore;
+use sqlx::MySqlPool;
+
+use crate::{
+    error::{api_error, api_success, ApiError, ApiErrorType},
+    mailer::{AppMailer, Mail},
+    modules::users::{
+        api_keys::util::revoke_api_keys,
+        model::UserModel,
+        sessions::util::revoke_sessions,
+        util::{hash_password, hash_token, validate_password},
+    },
+    AppState,
+};
+
+use super::model::{UserConfirmPasswordResetSchema, UserRequestPasswordResetSchema};
+
+// how long a reset email can be used for, in seconds
+const RESET_TOKEN_LIFETIME: i64 = 60 * 60;
+
+/// Creates a reset token for a user and emails it to them.
+async fn send_password_reset(
+    db: MySqlPool,
+    mailer: Arc<AppMailer>,
+    reset_url: Option<String>,
+    user: UserModel,
+    email: String,
+) -> Result<(), ApiError> {
+    let mut bytes = [0x00; 24];
+    rand::thread_rng().try_fill_bytes(&mut bytes).map_err(|e| {
+        error!("Can't fill bytes from RNG: {:?}", e);
+        api_error(ApiErrorType::ServerError, "Failed to generate reset token")
+    })?;
+    let token = bs58::encode(bytes).into_string();
+
+    // only the latest email works, even if two are requested at once
+    let mut transaction = db.begin().await?;
+    sqlx::query("DELETE FROM password_resets WHERE user_id = ?")
+        .bind(user.id)
+        .execute(&mut *transaction)
+        .await?;
+    sqlx::query("INSERT INTO password_resets (user_id, token_hash, expires_at) VALUES (?, ?, ?)")
+        .bind(user.id)
+        .bind(hash_token(&token))
+        .bind(Utc::now() + Duration::seconds(RESET_TOKEN_LIFETIME))
+        .execute(&mut *transaction)
+        .await?;
+    transaction.commit().await?;
+
+    let instructions = match reset_url {
+        Some(url) => format!(
+            "To choose a new password, open this link:\n\n{}",
+            url.replace("{token}", &token)
+        ),
+        None => format!(
+            "To choose a new password, enter this reset code:\n\n{}",
+            token
+        ),
+    };
+    let mail = Mail {
+        to: email,
+        subject: "Password reset".to_owned(),
+        body: format!(
+            "Hi {},\n\nSomeone asked to reset the password of your account. {}\n\nThis expires in an hour. If you didn't ask for this, you can ignore this email.",
+            user.name, instructions
+        ),
+    };
+
+    mailer.send(&mail).await.map_err(|e| {
+        error!("Failed to send password reset email: {}", e);
+        api_error(ApiErrorType::ServerError, "Failed to send email")
+    })
+}
+
+#[post("/request")]
+pub async fn user_request_password_reset_handler(
+    body: web::Json<UserRequestPasswordResetSchema>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let account = body.account.trim();
+    // anything with an @ is an email address, so a username can't be used to reach someone else's account
+    let query = match account.contains('@') {
+        true => "SELECT * FROM users WHERE email = ?",
+        false => "SELECT * FROM users WHERE name = ?",
+    };
+    let users = sqlx::query_as::<_, UserModel>(query)
+        .bind(account)
+        .fetch_all(&data.db)
+        .await?;
+
+    // emails aren't unique, so each account using the address gets its own reset email
+    for user in users {
+        if let Some(email) = user.email.clone().filter(|e| !e.is_empty()) {
+            // sent in the background, so how long this takes doesn't give away whether the account exists
+            let db = data.db.clone();
+            let mailer = data.mailer.clone();
+            let reset_url = data.config.get_string("password_reset_url").ok();
+            tokio::spawn(async move {
+                if let Err(e) = send_password_reset(db, mailer, reset_url, user, email).await {
+                    error!("Failed to start password reset: {}", e);
+                }
+            });
+        }
+    }
+
+    // the same response either way, so this can't be used to look up accounts
+    Ok(api_success("success"))
+}
+
+#[post("/confirm")]
+pub async fn user_confirm_password_reset_handler(
+    body: web::Json<UserConfirmPasswordResetSchema>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    // checked first, so a rejected password doesn't use up the code
+    validate_password(&body.new_password)?;
+
+    let (reset_id, user_id) = sqlx::query_as::<_, (i32, i32)>(
+        "SELECT id, user_id FROM password_resets WHERE token_hash = ? AND expires_at > NOW()",
+    )
+    .bind(hash_token(body.token.trim()))
+    .fetch_optional(&data.db)
+    .await?
+    .ok_or(api_error(
+        ApiErrorType::InvalidRequest,
+        "Invalid or expired reset code",
+    ))?;
+
+    let hash = hash_password(body.new_password.as_str())?;
+
+    let mut transaction = data.db.begin().await?;
+    // tokens are single use, so two requests racing with the same one can't both succeed
+    let deleted = sqlx::query("DELETE FROM password_resets WHERE id = ?")
+        .bind(reset_id)
+        .execute(&mut *transaction)
+        .await?;
+    if deleted.rows_affected() < 1 {
+        return Err(api_error(
+            ApiErrorType::InvalidRequest,
+            "Invalid or expired reset code",
+        ));
+    }
+
+    sqlx::query("UPDATE users SET pass = ? WHERE id = ?")
+        .bind(hash)
+        .bind(user_id)
+        .execute(&mut *transaction)
+        .await?;
+    transaction.commit().await?;
+
+    // whoever had the old password shouldn't stay logged in, or keep keys they made with it
+    revoke_sessions(&data.db, user_id, None).await?;
+    revoke_api_keys(&data.db, user_id).await?;
+
+    Ok(api_success("success"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use actix_web::{http::StatusCode, test, web, App};
+    use config::Config;
+    use serde_json::json;
+    use sqlx::MySqlPool;
+
+    use crate::{
+        booru_config::BooruConfig,
+        mailer::FileMailer,
+        modules::users::{model::UserModel, password_reset::scope, util::check_password},
+        storage::MemoryStorage,
+        AppState,
+    };
+
+    async fn test_state(db: MySqlPool, mail_dir: &Path) -> AppState {
+        let config = Config::builder()
+            .set_override("mail_file_path", mail_dir.to_str().unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+
+        AppState {
+            mailer: Arc::new(FileMailer::new(&config).await.unwrap()),
+            storage: Arc::new(MemoryStorage::new()),
+            booru_config: BooruConfig::new(&db).await,
+            db,
+            config,
+        }
+    }
+
+    async fn create_user(db: &MySqlPool, name: &str, email: &str) -> i32 {
+        sqlx::query("INSERT INTO users (name, pass, email) VALUES (?, NULL, ?)")
+            .bind(name)
+            .bind(email)
+            .execute(db)
+            .await
+            .unwrap()
+            .last_insert_id() as i32
+    }
+
+    /// Waits for the mailer to write `count` emails, since reset emails are sent in the background.
+    async fn wait_for_mail(dir: &Path, count: usize) -> Vec<String> {
+        for _ in 0..50 {
+            let mut mails = Vec::new();
+            let mut entries = tokio::fs::read_dir(dir).await.unwrap();
+            while let Some(entry) = entries.next_entry().await.unwrap() {
+                mails.push(tokio::fs::read_to_string(entry.path()).await.unwrap());
+            }
+
+            // files can be seen before they're fully written
+            if mails.len() >= count && mails.iter().all(|m| m.ends_with("ignore this email.\n")) {
+                return mails;
+            }
+            actix_web::rt::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        panic!("Expected {} emails", count);
+    }
+
+    fn reset_code(mail: &str) -> String {
+        mail.split("reset code:\n\n")
+            .nth(1)
+            .and_then(|rest| rest.lines().next())
+            .expect("No reset code in email")
+            .to_owned()
+    }
+
+    #[sqlx::test]
+    async fn reset_password_with_emailed_code(db: MySqlPool) {
+        let mail_dir = tempfile::tempdir().unwrap();
+        let user_id = create_user(&db, "alice", "alice@example.com").await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(
+                    test_state(db.clone(), mail_dir.path()).await,
+                ))
+                .service(scope()),
+        )
+        .await;
+
+        let request = test::TestRequest::post()
+            .uri("/password_reset/request")
+            .set_json(json!({ "account": "alice" }))
+            .to_request();
+        assert!(test::call_service(&app, request)
+            .await
+            .status()
+            .is_success());
+
+        let mails = wait_for_mail(mail_dir.path(), 1).await;
+        assert_eq!(mails.len(), 1);
+        assert!(mails[0].starts_with("To: alice@example.com\n"));
+        let token = reset_code(&mails[0]);
+
+        sqlx::query("INSERT INTO user_api_keys (user_id, name, prefix, key_hash, scopes) VALUES (?, 'bot', 'tk_abc', ?, 'read')")
+            .bind(user_id)
+            .bind("0".repeat(64))
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let confirm = |password: &str| {
+            test::TestRequest::post()
+                .uri("/password_reset/confirm")
+                .set_json(json!({ "token": token, "new_password": password }))
+                .to_request()
+        };
+
+        // a rejected password doesn't use up the code
+        assert_eq!(
+            test::call_service(&app, confirm("")).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+        assert!(test::call_service(&app, confirm("correct horse"))
+            .await
+            .status()
+            .is_success());
+
+        let user = sqlx::query_as::<_, UserModel>("SELECT * FROM users WHERE id = ?")
+            .bind(user_id)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert!(check_password(&user, "correct horse").unwrap());
+
+        let (keys,) =
+            sqlx::query_as::<_, (i64,)>("SELECT COUNT(*) FROM user_api_keys WHERE user_id = ?")
+                .bind(user_id)
+                .fetch_one(&db)
+                .await
+                .unwrap();
+        assert_eq!(keys, 0);
+
+        // codes only work once
+        assert_eq!(
+            test::call_service(&app, confirm("another horse"))
+                .await
+                .status(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[sqlx::test]
+    async fn reset_emails_every_account_with_the_address(db: MySqlPool) {
+        let mail_dir = tempfile::tempdir().unwrap();
+        create_user(&db, "alice", "shared@example.com").await;
+        create_user(&db, "alice_alt", "shared@example.com").await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(
+                    test_state(db.clone(), mail_dir.path()).await,
+                ))
+                .service(scope()),
+        )
+        .await;
+
+        let request = test::TestRequest::post()
+            .uri("/password_reset/request")
+            .set_json(json!({ "account": "shared@example.com" }))
+            .to_request();
+        assert!(test::call_service(&app, request)
+            .await
+            .status()
+            .is_success());
+
+        let mut mails = wait_for_mail(mail_dir.path(), 2).await;
+        mails.sort_by_key(|m| m.contains("Hi alice_alt,"));
+        assert_eq!(mails.len(), 2);
+        assert!(mails[0].contains("Hi alice,"));
+        assert!(mails[1].contains("Hi alice_alt,"));
+        assert!(mails
+            .iter()
+            .all(|m| m.starts_with("To: shared@example.com\n")));
+    }
+}
@@ -0,0 +1,10 @@
+use actix_web::{web, Scope};
+
+pub mod api;
+mod model;
+
+pub fn scope() -> Scope {
+    web::scope("/password_reset")
+        .service(api::user_request_password_reset_handler)
+        .service(api::user_confirm_password_reset_handler)
+}
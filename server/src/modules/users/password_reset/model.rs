@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UserRequestPasswordResetSchema {
+    /// The username or email of the account
+    pub account: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UserConfirmPasswordResetSchema {
+    pub token: String,
+    pub new_password: String,
+}
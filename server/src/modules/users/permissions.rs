@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{Executor, MySql};
+
+use crate::error::{api_error_owned, ApiError, ApiErrorType};
+
+use super::model::UserModel;
+
+/// The class new users are given, so it always has to exist.
+pub const DEFAULT_CLASS: &str = "user";
+
+/// Something only some user classes are allowed to do. Which classes have which is stored in the database.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    DeletePost,
+    /// Lock and unlock posts, and edit locked ones
+    LockPost,
+    /// Edit and add posts to pools owned by other users
+    EditAnyPool,
+    ManageTags,
+    /// Change the tags of every post matching a search at once
+    MassEditPosts,
+    ManageUsers,
+    /// Create user classes and change their permissions
+    ManageClasses,
+    /// Run maintenance jobs, and see every user's jobs
+    ManageSystem,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 8] = [
+        Permission::DeletePost,
+        Permission::LockPost,
+        Permission::EditAnyPool,
+        Permission::ManageTags,
+        Permission::MassEditPosts,
+        Permission::ManageUsers,
+        Permission::ManageClasses,
+        Permission::ManageSystem,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Permission::DeletePost => "delete_post",
+            Permission::LockPost => "lock_post",
+            Permission::EditAnyPool => "edit_any_pool",
+            Permission::ManageTags => "manage_tags",
+            Permission::MassEditPosts => "mass_edit_posts",
+            Permission::ManageUsers => "manage_users",
+            Permission::ManageClasses => "manage_classes",
+            Permission::ManageSystem => "manage_system",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Permission> {
+        Permission::ALL.into_iter().find(|p| p.name() == name)
+    }
+}
+
+/// Whether the user's class has a permission.
+pub async fn has_permission<'a, E: Executor<'a, Database = MySql>>(
+    db: E,
+    user: &UserModel,
+    permission: Permission,
+) -> Result<bool, ApiError> {
+    let (count,) = sqlx::query_as::<_, (i64,)>(
+        "SELECT COUNT(*) FROM user_class_permissions WHERE class = ? AND permission = ?",
+    )
+    .bind(&user.class)
+    .bind(permission.name())
+    .fetch_one(db)
+    .await?;
+
+    Ok(count > 0)
+}
+
+/// Fails with a Forbidden error unless the user's class has a permission.
+pub async fn require_permission<'a, E: Executor<'a, Database = MySql>>(
+    db: E,
+    user: &UserModel,
+    permission: Permission,
+) -> Result<(), ApiError> {
+    match has_permission(db, user, permission).await? {
+        true => Ok(()),
+        false => Err(api_error_owned(
+            ApiErrorType::Forbidden,
+            format!("Missing the {} permission", permission.name()),
+        )),
+    }
+}
+
+/// Whether `class` has any permission that `other_class` doesn't.
+pub async fn has_permissions_beyond<'a, E: Executor<'a, Database = MySql>>(
+    db: E,
+    class: &str,
+    other_class: &str,
+) -> Result<bool, ApiError> {
+    let (count,) = sqlx::query_as::<_, (i64,)>(
+        "SELECT COUNT(*) FROM user_class_permissions WHERE class = ? AND permission NOT IN (SELECT permission FROM user_class_permissions WHERE class = ?)",
+    )
+    .bind(class)
+    .bind(other_class)
+    .fetch_one(db)
+    .await?;
+
+    Ok(count > 0)
+}
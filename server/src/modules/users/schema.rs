@@ -19,3 +19,27 @@ pub struct UserSignupSchema {
     pub email: Option<String>,
     pub invite_code: Option<String>,
 }
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UserProfileSchema {
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UserChangePasswordSchema {
+    pub old_password: String,
+    pub new_password: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UserChangeEmailSchema {
+    /// The user's current password, since the email can be used to reset it
+    pub password: String,
+    /// The new email, or null to remove it
+    pub email: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UserRenameSchema {
+    pub name: String,
+}
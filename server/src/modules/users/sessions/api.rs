@@ -0,0 +1,124 @@
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
+use sqlx::MySqlPool;
+
+use crate::{
+    error::{api_error, api_success, ApiError, ApiErrorType},
+    modules::users::{
+        middleware::{get_session_id, get_user, AuthFactory},
+        permissions::{has_permissions_beyond, require_permission, Permission},
+    },
+    AppState,
+};
+
+use super::model::{
+    UserListSessionsResponse, UserRevokeSessionSchema, UserRevokeSessionsResponse,
+    UserRevokeUserSessionsSchema, UserSessionModel, UserSessionResponse,
+};
+use super::util::revoke_sessions;
+
+async fn list_user_sessions(
+    db: &MySqlPool,
+    user_id: i32,
+    current_session: Option<i32>,
+) -> Result<UserListSessionsResponse, ApiError> {
+    let models = sqlx::query_as::<_, UserSessionModel>(
+        "SELECT id, user_id, user_agent, ip, created_at, last_used_at, expires_at FROM user_sessions WHERE user_id = ? AND expires_at > NOW() ORDER BY last_used_at DESC",
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+
+    Ok(UserListSessionsResponse {
+        sessions: models
+            .iter()
+            .map(|m| UserSessionResponse::new(m, current_session))
+            .collect(),
+    })
+}
+
+#[get("/list", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn user_list_sessions_handler(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+
+    Ok(api_success(
+        list_user_sessions(&data.db, user.id, get_session_id(&req)).await?,
+    ))
+}
+
+#[post("/revoke", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn user_revoke_session_handler(
+    body: web::Json<UserRevokeSessionSchema>,
+    data: web::Data<AppState>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+
+    sqlx::query("DELETE FROM user_sessions WHERE user_id = ? AND id = ?")
+        .bind(user.id)
+        .bind(body.session_id)
+        .execute(&data.db)
+        .await?;
+
+    Ok(api_success(
+        list_user_sessions(&data.db, user.id, get_session_id(&req)).await?,
+    ))
+}
+
+#[post("/revoke_others", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn user_revoke_other_sessions_handler(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+
+    let current_session = get_session_id(&req);
+    revoke_sessions(&data.db, user.id, current_session).await?;
+
+    Ok(api_success(
+        list_user_sessions(&data.db, user.id, current_session).await?,
+    ))
+}
+
+#[post("/revoke_user", wrap = "AuthFactory { reject_unauthed: true }")]
+pub async fn user_revoke_user_sessions_handler(
+    body: web::Json<UserRevokeUserSessionsSchema>,
+    data: web::Data<AppState>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user(&req).ok_or(api_error(ApiErrorType::AuthorizationFailed, "Missing user"))?;
+
+    require_permission(&data.db, &user, Permission::ManageUsers).await?;
+
+    let revoked = match body.user_id {
+        Some(user_id) => {
+            let (class,) = sqlx::query_as::<_, (String,)>("SELECT class FROM users WHERE id = ?")
+                .bind(user_id)
+                .fetch_optional(&data.db)
+                .await?
+                .ok_or(api_error(ApiErrorType::InvalidRequest, "User not found"))?;
+
+            // same rule as the other admin actions on a user
+            if has_permissions_beyond(&data.db, &class, &user.class).await? {
+                return Err(api_error(
+                    ApiErrorType::Forbidden,
+                    "User's class has permissions your class doesn't",
+                ));
+            }
+
+            revoke_sessions(&data.db, user_id, None).await?
+        }
+        None => sqlx::query("DELETE FROM user_sessions")
+            .execute(&data.db)
+            .await?
+            .rows_affected(),
+    };
+
+    Ok(api_success(UserRevokeSessionsResponse { revoked }))
+}
@@ -0,0 +1,13 @@
+use actix_web::{web, Scope};
+
+pub mod api;
+pub mod model;
+pub mod util;
+
+pub fn scope() -> Scope {
+    web::scope("/session")
+        .service(api::user_list_sessions_handler)
+        .service(api::user_revoke_session_handler)
+        .service(api::user_revoke_other_sessions_handler)
+        .service(api::user_revoke_user_sessions_handler)
+}
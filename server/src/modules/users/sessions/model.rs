@@ -0,0 +1,60 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct UserSessionModel {
+    pub id: i32,
+    pub user_id: i32,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UserSessionResponse {
+    pub id: i32,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// Whether this is the session the request was made with
+    pub current: bool,
+}
+
+impl UserSessionResponse {
+    pub fn new(model: &UserSessionModel, current_session: Option<i32>) -> UserSessionResponse {
+        UserSessionResponse {
+            id: model.id,
+            user_agent: model.user_agent.clone(),
+            ip: model.ip.clone(),
+            created_at: model.created_at,
+            last_used_at: model.last_used_at,
+            expires_at: model.expires_at,
+            current: current_session == Some(model.id),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UserListSessionsResponse {
+    pub sessions: Vec<UserSessionResponse>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UserRevokeSessionSchema {
+    pub session_id: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UserRevokeUserSessionsSchema {
+    /// The user to log out, or every user if missing
+    pub user_id: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UserRevokeSessionsResponse {
+    pub revoked: u64,
+}
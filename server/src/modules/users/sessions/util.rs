@@ -0,0 +1,124 @@
+use actix_web::HttpRequest;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::MySqlPool;
+use substring::Substring;
+use uuid::Uuid;
+
+use crate::error::{api_error, ApiError, ApiErrorType};
+
+/// How long a session lasts when the user asked to be remembered.
+/// Otherwise it only lasts as long as its first access token.
+pub const REMEMBERED_SESSION_LIFETIME: i64 = 60 * 60 * 24 * 30;
+
+/// Where a session's tokens are being used from.
+pub struct SessionClient {
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+}
+
+impl SessionClient {
+    pub fn from_request(req: &HttpRequest) -> SessionClient {
+        SessionClient {
+            user_agent: req
+                .headers()
+                .get("User-Agent")
+                .and_then(|h| h.to_str().ok())
+                .map(|s| s.substring(0, 255).to_owned()),
+            ip: req
+                .connection_info()
+                .realip_remote_addr()
+                .map(|s| s.to_owned()),
+        }
+    }
+}
+
+/// Records a new session, returning the ID its tokens carry.
+pub async fn create_session(
+    db: &MySqlPool,
+    user_id: i32,
+    client: &SessionClient,
+    expires: DateTime<Utc>,
+) -> Result<String, ApiError> {
+    // expired sessions are never used again, so this is as good a time as any to clean them up
+    sqlx::query("DELETE FROM user_sessions WHERE expires_at < NOW()")
+        .execute(db)
+        .await?;
+
+    let token_id = Uuid::new_v4().simple().to_string();
+    sqlx::query("INSERT INTO user_sessions (user_id, token_id, user_agent, ip, expires_at) VALUES (?, ?, ?, ?, ?)")
+        .bind(user_id)
+        .bind(&token_id)
+        .bind(&client.user_agent)
+        .bind(&client.ip)
+        .bind(expires)
+        .execute(db)
+        .await?;
+
+    Ok(token_id)
+}
+
+/// Checks that the session a token belongs to hasn't been revoked or expired,
+/// returning its ID and expiration time.
+pub async fn check_session(
+    db: &MySqlPool,
+    user_id: i32,
+    token_id: &str,
+) -> Result<(i32, DateTime<Utc>), ApiError> {
+    let session = sqlx::query_as::<_, (i32, DateTime<Utc>)>(
+        "SELECT id, expires_at FROM user_sessions WHERE token_id = ? AND user_id = ? AND expires_at > NOW()",
+    )
+    .bind(token_id)
+    .bind(user_id)
+    .fetch_optional(db)
+    .await?
+    .ok_or(api_error(
+        ApiErrorType::AuthorizationFailed,
+        "Session has been revoked or expired",
+    ))?;
+
+    // only written once a minute, so every request doesn't need a write
+    sqlx::query("UPDATE user_sessions SET last_used_at = NOW() WHERE id = ? AND last_used_at < NOW() - INTERVAL 1 MINUTE")
+        .bind(session.0)
+        .execute(db)
+        .await?;
+
+    Ok(session)
+}
+
+/// Records where a session was last used from, when its access token is refreshed.
+pub async fn update_session_client(
+    db: &MySqlPool,
+    session_id: i32,
+    client: &SessionClient,
+) -> Result<(), ApiError> {
+    sqlx::query(
+        "UPDATE user_sessions SET user_agent = ?, ip = ?, last_used_at = NOW() WHERE id = ?",
+    )
+    .bind(&client.user_agent)
+    .bind(&client.ip)
+    .bind(session_id)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Revokes every session of a user, apart from `except`. Returns how many were revoked.
+pub async fn revoke_sessions(
+    db: &MySqlPool,
+    user_id: i32,
+    except: Option<i32>,
+) -> Result<u64, ApiError> {
+    let result = sqlx::query("DELETE FROM user_sessions WHERE user_id = ? AND id != ?")
+        .bind(user_id)
+        .bind(except.unwrap_or(0))
+        .execute(db)
+        .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// When a new access token should expire, which can't be after its session does.
+pub fn access_token_expiration(ttl: i64, session_expires: DateTime<Utc>) -> DateTime<Utc> {
+    (Utc::now() + Duration::seconds(ttl)).min(session_expires)
+}
@@ -2,15 +2,17 @@ use std::fmt;
 
 use actix_web::{dev::ServiceRequest, http::header::HeaderValue, HttpRequest};
 use base64::{engine::general_purpose, Engine as _};
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Utc};
 use jsonwebtoken::{DecodingKey, EncodingKey, Header, TokenData, Validation};
 use log::error;
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use sqlx::MySqlPool;
 
 use crate::error::{api_error, ApiError, ApiErrorType};
 
+use super::bans::check_not_banned;
 use super::model::{AuthTokenResponse, UserModel};
 
 static SECRET: Lazy<String> =
@@ -22,10 +24,6 @@ pub fn standardize_php_hash(hash: String) -> String {
     hash.replace("$2y$", "$2a$")
 }
 
-pub fn phpize_bcrypt_hash(hash: String) -> String {
-    hash.replace("$2b$", "$2y$")
-}
-
 #[derive(Debug, PartialEq)]
 pub enum AuthTokenKind {
     Access,
@@ -45,20 +43,19 @@ struct AuthTokenClaims {
     exp: i64,
     name: String,
     id: i32,
+    /// Session the token belongs to, so it stops working once the session is revoked.
+    /// Tokens issued before sessions existed don't have one, and aren't accepted any more.
+    #[serde(default)]
+    sid: Option<String>,
 }
 
 pub fn create_auth_token(
     user: &UserModel,
+    session: &str,
     kind: AuthTokenKind,
+    expiration: DateTime<Utc>,
 ) -> Result<AuthTokenResponse, jsonwebtoken::errors::Error> {
-    let expiration: chrono::DateTime<Utc> = Utc::now()
-        + Duration::seconds(match kind {
-            AuthTokenKind::Access => 60 * 60 * 24,
-            AuthTokenKind::Refresh => 60 * 60 * 24 * 30,
-        });
-
     let expiration_str = expiration.to_rfc3339();
-    let _user_id = user.id.to_string();
 
     let claims = AuthTokenClaims {
         kind: match kind {
@@ -69,6 +66,7 @@ pub fn create_auth_token(
         name: user.name.clone(),
         id: user.id.clone(),
         exp: expiration.timestamp(),
+        sid: Some(session.to_owned()),
     };
 
     let token: String = jsonwebtoken::encode(&Header::default(), &claims, &*ENCODING_KEY)?;
@@ -78,10 +76,11 @@ pub fn create_auth_token(
     })
 }
 
+/// Returns the user ID and session of a valid token.
 pub fn validate_auth_header(
     req: &ServiceRequest,
     expected_kind: AuthTokenKind,
-) -> Result<i32, &'static str> {
+) -> Result<(i32, String), &'static str> {
     let authorization_header: &HeaderValue = req
         .headers()
         .get("Authorization")
@@ -106,7 +105,10 @@ pub fn validate_auth_header(
     validate_auth_token(token, expected_kind)
 }
 
-pub fn validate_auth_token(token: &str, expected_kind: AuthTokenKind) -> Result<i32, &'static str> {
+pub fn validate_auth_token(
+    token: &str,
+    expected_kind: AuthTokenKind,
+) -> Result<(i32, String), &'static str> {
     let token: TokenData<AuthTokenClaims> =
         jsonwebtoken::decode::<AuthTokenClaims>(token, &*DECODING_KEY, &Validation::default())
             .map_err(|e| {
@@ -124,7 +126,13 @@ pub fn validate_auth_token(token: &str, expected_kind: AuthTokenKind) -> Result<
         return Err("Token type mismatch");
     }
 
-    return Ok(token.claims.id);
+    // these can't be revoked, so they'd outlive password changes and bans
+    let session = token
+        .claims
+        .sid
+        .ok_or("Token was issued before sessions were added, log in again")?;
+
+    return Ok((token.claims.id, session));
 }
 
 pub async fn check_user(
@@ -137,7 +145,8 @@ pub async fn check_user(
         .await
     {
         Ok(user) => match check_password(&user, &password) {
-            Ok(true) => Ok(user),
+            // only said after the password is checked, so bans can't be looked up by anyone
+            Ok(true) => check_not_banned(db, user.id).await.map(|_| user),
             Ok(false) => Err(api_error(
                 ApiErrorType::AuthorizationFailed,
                 "Invalid credentials",
@@ -159,7 +168,11 @@ pub async fn check_user(
 }
 
 pub fn check_password(user: &UserModel, plaintext: &str) -> Result<bool, ApiError> {
-    let pass_hash = standardize_php_hash(user.pass.clone().unwrap_or("".to_owned()));
+    // deleted accounts don't have a password, so nobody can log in to them
+    let Some(pass) = user.pass.clone() else {
+        return Ok(false);
+    };
+    let pass_hash = standardize_php_hash(pass);
 
     bcrypt::verify(plaintext, &pass_hash).map_err(|e| {
         error!("Bcrypt error: {:?}", e);
@@ -170,16 +183,72 @@ pub fn check_password(user: &UserModel, plaintext: &str) -> Result<bool, ApiErro
     })
 }
 
+/// Checks that a new password is acceptable, before anything is changed.
+pub fn validate_password(password: &str) -> Result<(), ApiError> {
+    if password.is_empty() {
+        return Err(api_error(
+            ApiErrorType::InvalidRequest,
+            "Password can't be empty",
+        ));
+    }
+
+    Ok(())
+}
+
 pub fn hash_password(password: &str) -> Result<String, ApiError> {
-    bcrypt::hash(password, 10)
-        .and_then(|h| Ok(phpize_bcrypt_hash(h)))
-        .map_err(|e| {
-            error!("Bcrypt error: {:?}", e);
-            api_error(
-                ApiErrorType::ServerError,
-                "Password hashing went wrong somehow",
-            )
-        })
+    validate_password(password)?;
+
+    bcrypt::hash(password, 10).map_err(|e| {
+        error!("Bcrypt error: {:?}", e);
+        api_error(
+            ApiErrorType::ServerError,
+            "Password hashing went wrong somehow",
+        )
+    })
+}
+
+/// Trims a new username, checking that it fits in the users table.
+/// Names are limited to ASCII letters, digits, `_`, `.` and `-`, so they can't be made to look like someone else's
+/// with lookalike characters or invisible ones, and they never look like an email address.
+pub fn validate_username(name: &str) -> Result<String, ApiError> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > 32 {
+        return Err(api_error(
+            ApiErrorType::InvalidRequest,
+            "Usernames must be between 1 and 32 characters",
+        ));
+    }
+
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-')
+    {
+        return Err(api_error(
+            ApiErrorType::InvalidRequest,
+            "Usernames can only contain letters, numbers, _, . and -",
+        ));
+    }
+
+    Ok(name.to_owned())
+}
+
+/// Trims a new email, treating an empty one as no email.
+pub fn validate_email(email: Option<&String>) -> Result<Option<String>, ApiError> {
+    let Some(email) = email.map(|e| e.trim()).filter(|e| !e.is_empty()) else {
+        return Ok(None);
+    };
+
+    if !email.contains('@') || email.chars().count() > 128 {
+        return Err(api_error(ApiErrorType::InvalidRequest, "Invalid email"));
+    }
+
+    Ok(Some(email.to_owned()))
+}
+
+/// Hashes a random token, like an API key, for storing in the database.
+/// They're long enough that they don't need a slow hash like passwords do.
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
 }
 
 pub fn get_basic_auth_header(req: &HttpRequest) -> Option<(String, String)> {
@@ -0,0 +1,155 @@
+use std::path::{Component, Path, PathBuf};
+
+use async_trait::async_trait;
+use reqwest::Url;
+
+use super::{signing, StorageBackend, StorageError, StoredFile};
+
+/// Stores files in a local directory, to be served by something like nginx.
+pub struct LocalStorage {
+    root: PathBuf,
+    base_url: String,
+    /// The path part of base_url, which is what the web server checks signatures against
+    base_path: String,
+    /// Secret shared with the web server for signing URLs. URLs aren't signed without one.
+    url_secret: Option<String>,
+    url_ttl: u32,
+}
+
+impl LocalStorage {
+    pub async fn new(config: &config::Config, url_ttl: u32) -> Result<LocalStorage, StorageError> {
+        let root = PathBuf::from(
+            config
+                .get_string("local_storage_path")
+                .unwrap_or("data/storage".to_owned()),
+        );
+
+        let base_url = config
+            .get_string("local_storage_url")
+            .unwrap_or("/storage".to_owned());
+
+        let base_url = base_url.trim_end_matches('/').to_owned();
+        let base_path = match Url::parse(&base_url) {
+            Ok(url) => url.path().trim_end_matches('/').to_owned(),
+            // relative to wherever the client is served from
+            Err(_) => base_url.clone(),
+        };
+
+        for dir in ["images", "thumbs"] {
+            tokio::fs::create_dir_all(root.join(dir)).await?;
+        }
+
+        Ok(LocalStorage {
+            root,
+            base_url,
+            base_path,
+            url_secret: std::env::var("STORAGE_URL_SECRET").ok(),
+            url_ttl,
+        })
+    }
+
+    /// Resolves a storage path inside the root directory, refusing anything that could escape it.
+    fn resolve(&self, path: &str) -> Result<PathBuf, StorageError> {
+        let relative = Path::new(path);
+        if !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Err(StorageError::NotFound(path.to_owned()));
+        }
+
+        Ok(self.root.join(relative))
+    }
+}
+
+#[async_trait]
+impl StorageBackend for LocalStorage {
+    async fn get_file(&self, path: String) -> Result<Vec<u8>, StorageError> {
+        let full_path = self.resolve(&path)?;
+        tokio::fs::read(full_path)
+            .await
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => StorageError::NotFound(path),
+                _ => e.into(),
+            })
+    }
+
+    async fn put_file(&self, path: String, data: &[u8]) -> Result<(), StorageError> {
+        let full_path = self.resolve(&path)?;
+        if let Some(parent) = full_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        // write to a temporary file first so a partially written file is never served
+        let temp_path = full_path.with_extension("partial");
+        tokio::fs::write(&temp_path, data).await?;
+        tokio::fs::rename(&temp_path, &full_path).await?;
+
+        Ok(())
+    }
+
+    async fn delete_file(&self, path: String) -> Result<(), StorageError> {
+        let full_path = self.resolve(&path)?;
+        match tokio::fs::remove_file(full_path).await {
+            Ok(_) => Ok(()),
+            // S3 doesn't complain about deleting missing files either
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn list(&self, prefix: String) -> Result<Vec<StoredFile>, StorageError> {
+        let prefix = prefix.trim_end_matches('/');
+        let dir = self.resolve(prefix)?;
+
+        let mut paths = Vec::new();
+        let mut entries = match tokio::fs::read_dir(dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(paths),
+            Err(e) => return Err(e.into()),
+        };
+
+        while let Some(entry) = entries.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            if !metadata.is_file() {
+                continue;
+            }
+
+            let name = entry.file_name().to_string_lossy().into_owned();
+            // leftovers from interrupted writes
+            if name.ends_with(".partial") {
+                continue;
+            }
+
+            paths.push(StoredFile {
+                path: format!("{}/{}", prefix, name),
+                modified: metadata.modified().ok().map(|m| m.into()),
+            });
+        }
+
+        Ok(paths)
+    }
+
+    fn file_url(&self, path: String) -> String {
+        format!("{}/{}", self.base_url, path)
+    }
+
+    fn signed_url(&self, path: String) -> Result<String, StorageError> {
+        match &self.url_secret {
+            Some(secret) => Ok(signing::sign_url(
+                &self.file_url(path.clone()),
+                &format!("{}/{}", self.base_path, path),
+                self.url_ttl,
+                secret,
+            )),
+            None => Ok(self.file_url(path)),
+        }
+    }
+
+    fn verify_signed_uri(&self, uri: &str) -> bool {
+        match &self.url_secret {
+            Some(secret) => signing::verify_uri(uri, secret),
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+
+use super::{StorageBackend, StorageError, StoredFile};
+
+/// Keeps files in memory. Everything is lost when the server stops, so this is only useful for testing.
+pub struct MemoryStorage {
+    /// Each file's content and when it was written
+    files: DashMap<String, (Vec<u8>, DateTime<Utc>)>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> MemoryStorage {
+        MemoryStorage {
+            files: DashMap::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl StorageBackend for MemoryStorage {
+    async fn get_file(&self, path: String) -> Result<Vec<u8>, StorageError> {
+        self.files
+            .get(&path)
+            .map(|f| f.value().0.clone())
+            .ok_or(StorageError::NotFound(path))
+    }
+
+    async fn put_file(&self, path: String, data: &[u8]) -> Result<(), StorageError> {
+        self.files.insert(path, (data.to_vec(), Utc::now()));
+        Ok(())
+    }
+
+    async fn delete_file(&self, path: String) -> Result<(), StorageError> {
+        self.files.remove(&path);
+        Ok(())
+    }
+
+    async fn list(&self, prefix: String) -> Result<Vec<StoredFile>, StorageError> {
+        Ok(self
+            .files
+            .iter()
+            .filter(|f| f.key().starts_with(&prefix))
+            .map(|f| StoredFile {
+                path: f.key().clone(),
+                modified: Some(f.value().1),
+            })
+            .collect())
+    }
+
+    fn file_url(&self, path: String) -> String {
+        format!("memory://{}", path)
+    }
+}
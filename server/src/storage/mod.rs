@@ -0,0 +1,435 @@
+use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use ::s3::error::S3Error;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use once_cell::unsync::Lazy;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::Mutex;
+
+mod local;
+mod memory;
+mod s3;
+pub mod signing;
+
+pub use local::LocalStorage;
+pub use memory::MemoryStorage;
+pub use s3::S3Storage;
+
+#[derive(Debug, Error)]
+pub enum StorageError {
+    #[error("S3 error: {0}")]
+    S3(#[from] S3Error),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("file not found: {0}")]
+    NotFound(String),
+    #[error("storage misconfigured: {0}")]
+    Config(String),
+}
+
+fn image_path(hash: String) -> String {
+    format!("images/{}", hash)
+}
+
+fn thumb_path(hash: String) -> String {
+    format!("thumbs/{}", hash)
+}
+
+/// The downscaled copies kept of each post.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rendition {
+    /// Small thumbnail for post grids
+    Thumb,
+    /// Large preview for post pages
+    Preview,
+    /// Mid-size copy of huge images, between the preview and the original
+    Sample,
+    /// Short muted clip from the middle of a video
+    Clip,
+    /// Sprite sheet of frames taken at regular intervals through a video, for hover scrubbing
+    Storyboard,
+}
+
+impl Rendition {
+    pub const ALL: [Rendition; 5] = [
+        Rendition::Thumb,
+        Rendition::Preview,
+        Rendition::Sample,
+        Rendition::Clip,
+        Rendition::Storyboard,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rendition::Thumb => "thumb",
+            Rendition::Preview => "preview",
+            Rendition::Sample => "sample",
+            Rendition::Clip => "clip",
+            Rendition::Storyboard => "storyboard",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Rendition> {
+        match value {
+            "thumb" => Some(Rendition::Thumb),
+            "preview" => Some(Rendition::Preview),
+            "sample" => Some(Rendition::Sample),
+            "clip" => Some(Rendition::Clip),
+            "storyboard" => Some(Rendition::Storyboard),
+            _ => None,
+        }
+    }
+}
+
+/// Formats renditions are encoded in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RenditionFormat {
+    Webp,
+    Avif,
+    /// Fallback for clients without WebP or AVIF support, always created alongside them
+    Jpeg,
+    /// Only used for video clips
+    Mp4,
+}
+
+impl RenditionFormat {
+    pub fn ext(&self) -> &'static str {
+        match self {
+            RenditionFormat::Webp => "webp",
+            RenditionFormat::Avif => "avif",
+            RenditionFormat::Jpeg => "jpeg",
+            RenditionFormat::Mp4 => "mp4",
+        }
+    }
+
+    pub fn mime(&self) -> &'static str {
+        match self {
+            RenditionFormat::Webp => "image/webp",
+            RenditionFormat::Avif => "image/avif",
+            RenditionFormat::Jpeg => "image/jpeg",
+            RenditionFormat::Mp4 => "video/mp4",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<RenditionFormat> {
+        match value {
+            "webp" => Some(RenditionFormat::Webp),
+            "avif" => Some(RenditionFormat::Avif),
+            "jpeg" => Some(RenditionFormat::Jpeg),
+            "mp4" => Some(RenditionFormat::Mp4),
+            _ => None,
+        }
+    }
+}
+
+fn rendition_path(hash: String, rendition: Rendition, format: RenditionFormat) -> String {
+    match (rendition, format) {
+        // JPEG thumbnails predate renditions, so they keep their old location
+        (Rendition::Thumb, RenditionFormat::Jpeg) => thumb_path(hash),
+        _ => format!("renditions/{}/{}.{}", rendition.name(), hash, format.ext()),
+    }
+}
+
+/// A file found by [StorageBackend::list].
+#[derive(Debug, Clone)]
+pub struct StoredFile {
+    pub path: String,
+    /// When the file was last written, if the backend can tell
+    pub modified: Option<DateTime<Utc>>,
+}
+
+/// Somewhere post content and thumbnails are kept.
+/// Backends only need to deal with raw paths, images and thumbs are laid out the same way in all of them.
+#[async_trait]
+pub trait StorageBackend: Send + Sync {
+    async fn get_file(&self, path: String) -> Result<Vec<u8>, StorageError>;
+    async fn put_file(&self, path: String, data: &[u8]) -> Result<(), StorageError>;
+    async fn delete_file(&self, path: String) -> Result<(), StorageError>;
+    /// Lists the paths of every file under a directory, like `images/`.
+    async fn list(&self, prefix: String) -> Result<Vec<StoredFile>, StorageError>;
+    /// The public URL a file can be downloaded from.
+    fn file_url(&self, path: String) -> String;
+
+    /// A URL for a file that stops working after the configured `media_url_ttl`.
+    /// Backends that can't sign URLs return the public URL.
+    fn signed_url(&self, path: String) -> Result<String, StorageError> {
+        Ok(self.file_url(path))
+    }
+
+    /// Checks a request URI produced by [StorageBackend::signed_url],
+    /// for web servers that ask us whether to serve each file.
+    fn verify_signed_uri(&self, _uri: &str) -> bool {
+        false
+    }
+
+    fn image_path(&self, hash: String) -> String {
+        image_path(hash)
+    }
+
+    fn thumb_path(&self, hash: String) -> String {
+        thumb_path(hash)
+    }
+
+    fn rendition_path(
+        &self,
+        hash: String,
+        rendition: Rendition,
+        format: RenditionFormat,
+    ) -> String {
+        rendition_path(hash, rendition, format)
+    }
+
+    fn image_url(&self, hash: String) -> String {
+        self.file_url(self.image_path(hash))
+    }
+
+    fn thumb_url(&self, hash: String) -> String {
+        self.file_url(self.thumb_path(hash))
+    }
+
+    fn signed_image_url(&self, hash: String) -> Result<String, StorageError> {
+        self.signed_url(self.image_path(hash))
+    }
+
+    fn signed_thumb_url(&self, hash: String) -> Result<String, StorageError> {
+        self.signed_url(self.thumb_path(hash))
+    }
+
+    fn rendition_url(&self, hash: String, rendition: Rendition, format: RenditionFormat) -> String {
+        self.file_url(self.rendition_path(hash, rendition, format))
+    }
+
+    fn signed_rendition_url(
+        &self,
+        hash: String,
+        rendition: Rendition,
+        format: RenditionFormat,
+    ) -> Result<String, StorageError> {
+        self.signed_url(self.rendition_path(hash, rendition, format))
+    }
+
+    async fn get_image(&self, hash: String) -> Result<Vec<u8>, StorageError> {
+        self.get_file(self.image_path(hash)).await
+    }
+
+    async fn put_image(&self, hash: String, data: &[u8]) -> Result<(), StorageError> {
+        self.put_file(self.image_path(hash), data).await
+    }
+
+    async fn put_thumb(&self, hash: String, data: &[u8]) -> Result<(), StorageError> {
+        self.put_file(self.thumb_path(hash), data).await
+    }
+
+    async fn put_rendition(
+        &self,
+        hash: String,
+        rendition: Rendition,
+        format: RenditionFormat,
+        data: &[u8],
+    ) -> Result<(), StorageError> {
+        self.put_file(self.rendition_path(hash, rendition, format), data)
+            .await
+    }
+}
+
+pub type AppStorage = dyn StorageBackend;
+
+/// Creates the storage backend selected by the `storage` config value.
+pub async fn create_storage(config: &config::Config) -> Result<Arc<AppStorage>, StorageError> {
+    let backend = config.get_string("storage").unwrap_or("s3".to_owned());
+    // how long signed media URLs stay valid, in seconds
+    let url_ttl = config.get_int("media_url_ttl").unwrap_or(60 * 60) as u32;
+
+    let storage: Arc<AppStorage> = match backend.as_str() {
+        "s3" => Arc::new(S3Storage::new(config, url_ttl)?),
+        "local" => Arc::new(LocalStorage::new(config, url_ttl).await?),
+        "memory" => Arc::new(MemoryStorage::new()),
+        _ => {
+            return Err(StorageError::Config(format!(
+                "unknown storage backend '{}'",
+                backend
+            )))
+        }
+    };
+
+    Ok(storage)
+}
+
+type DataManagerPtr = Arc<Mutex<DataManagerInner>>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TempFile(u64, PathBuf);
+
+impl TempFile {
+    /// Deletes this temporary file.
+    pub fn close(&self, data_manager: DataManagerPtr) {}
+}
+
+impl Deref for TempFile {
+    type Target = Path;
+
+    fn deref(&self) -> &Self::Target {
+        self.1.as_path()
+    }
+}
+
+/// Manages temp files and other things in the data/ directory.
+pub struct DataManagerInner {
+    db: sled::Db,
+    dir: PathBuf,
+    temp_dir: PathBuf,
+}
+
+#[derive(Clone)]
+pub struct DataManager(DataManagerPtr);
+
+impl DataManager {
+    pub fn new() -> Self {
+        let dir: PathBuf = PathBuf::from("data");
+        fs::create_dir(&dir).unwrap();
+        let sled = sled::open(dir.clone().join("cache")).unwrap();
+        let temp_dir = dir.clone().join("temp");
+
+        let unknown_files = fs::read_dir(&temp_dir).unwrap().filter(|d| {
+            sled.get(d.as_ref().unwrap().file_name().as_encoded_bytes())
+                .unwrap()
+                .is_none()
+        });
+
+        for f in unknown_files {
+            fs::remove_file(temp_dir.clone().join(f.unwrap().file_name())).unwrap();
+        }
+
+        DataManager(Arc::new(Mutex::new(DataManagerInner {
+            dir,
+            db: sled,
+            temp_dir,
+        })))
+    }
+
+    /// Creates and records a new temp file.
+    /// The file will not be deleted until you call [TempFile::close()].
+    pub async fn temp_file(&self) -> anyhow::Result<TempFile> {
+        let dm = self.0.lock().await;
+        let dir = dm.temp_dir.clone();
+
+        let mut gen = srfng::Generator::new();
+        let filename: String;
+        loop {
+            let new_file = gen.generate();
+            if !dir.clone().join(&new_file).is_file() {
+                filename = new_file;
+                break;
+            }
+        }
+
+        let mut s = DefaultHasher::default();
+        filename.hash(&mut s);
+        let id = s.finish();
+        let path = dir.join(&filename);
+        let temp = TempFile(id, path);
+
+        // record temp file in the database
+        dm.db
+            .insert(id.to_le_bytes(), serde_json::to_string(&temp)?.as_bytes())?;
+
+        Ok(temp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = "0123456789abcdef";
+
+    /// Takes an image through being written, read, listed, overwritten and deleted.
+    async fn round_trip(storage: &AppStorage) {
+        assert!(matches!(
+            storage.get_image(HASH.to_owned()).await,
+            Err(StorageError::NotFound(_))
+        ));
+
+        storage
+            .put_image(HASH.to_owned(), b"image data")
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.get_image(HASH.to_owned()).await.unwrap(),
+            b"image data"
+        );
+
+        let listed = storage.list("images/".to_owned()).await.unwrap();
+        assert_eq!(
+            listed.iter().map(|f| f.path.as_str()).collect::<Vec<_>>(),
+            vec![format!("images/{}", HASH)]
+        );
+        assert!(listed[0].modified.is_some());
+
+        // writing again replaces the file
+        storage
+            .put_image(HASH.to_owned(), b"new data")
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.get_image(HASH.to_owned()).await.unwrap(),
+            b"new data"
+        );
+
+        storage
+            .delete_file(storage.image_path(HASH.to_owned()))
+            .await
+            .unwrap();
+        assert!(matches!(
+            storage.get_image(HASH.to_owned()).await,
+            Err(StorageError::NotFound(_))
+        ));
+        assert!(storage.list("images/".to_owned()).await.unwrap().is_empty());
+
+        // deleting a file that isn't there isn't an error
+        storage
+            .delete_file(storage.image_path(HASH.to_owned()))
+            .await
+            .unwrap();
+    }
+
+    #[actix_web::test]
+    async fn memory_storage_round_trip() {
+        let storage = MemoryStorage::new();
+        round_trip(&storage).await;
+
+        assert_eq!(
+            storage.image_url(HASH.to_owned()),
+            format!("memory://images/{}", HASH)
+        );
+    }
+
+    #[actix_web::test]
+    async fn local_storage_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = config::Config::builder()
+            .set_override("local_storage_path", dir.path().to_str().unwrap())
+            .unwrap()
+            .set_override("local_storage_url", "https://example.com/storage/")
+            .unwrap()
+            .build()
+            .unwrap();
+        let storage = LocalStorage::new(&config, 3600).await.unwrap();
+        round_trip(&storage).await;
+
+        assert_eq!(
+            storage.image_url(HASH.to_owned()),
+            format!("https://example.com/storage/images/{}", HASH)
+        );
+        // paths can't reach outside the storage directory
+        assert!(storage.get_file("../outside".to_owned()).await.is_err());
+    }
+}
@@ -0,0 +1,98 @@
+use ::s3::creds::Credentials;
+use ::s3::error::S3Error;
+use ::s3::{Bucket, Region};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use super::{StorageBackend, StorageError, StoredFile};
+
+/// Stores files in an S3 bucket, or a bucket on an S3-compatible host.
+pub struct S3Storage {
+    bucket: Bucket,
+    endpoint_url: String,
+    url_ttl: u32,
+}
+
+// the longest S3 allows a presigned URL to stay valid
+const MAX_URL_TTL: u32 = 60 * 60 * 24 * 7;
+
+impl S3Storage {
+    pub fn new(config: &config::Config, url_ttl: u32) -> Result<S3Storage, StorageError> {
+        if url_ttl > MAX_URL_TTL {
+            return Err(StorageError::Config(format!(
+                "media_url_ttl can't be more than {} seconds with S3 storage",
+                MAX_URL_TTL
+            )));
+        }
+
+        let endpoint_url = std::env::var("AWS_ENDPOINT").map_err(|_| {
+            StorageError::Config("missing S3 endpoint url in environment".to_owned())
+        })?;
+
+        let bucket = config
+            .get_string("s3_bucket")
+            .map_err(|_| StorageError::Config("missing S3 bucket name in config".to_owned()))?;
+
+        let credentials = Credentials::from_env()
+            .map_err(|e| StorageError::Config(format!("failed to create credentials: {}", e)))?;
+
+        let region = Region::from_default_env()
+            .map_err(|e| StorageError::Config(format!("can't create region: {}", e)))?;
+
+        let bucket = Bucket::new(bucket.as_str(), region, credentials)?;
+
+        Ok(S3Storage {
+            bucket,
+            endpoint_url,
+            url_ttl,
+        })
+    }
+}
+
+#[async_trait]
+impl StorageBackend for S3Storage {
+    async fn get_file(&self, path: String) -> Result<Vec<u8>, StorageError> {
+        // missing files come back as a 404 rather than their own error
+        let response_data = match self.bucket.get_object(path.clone()).await {
+            Err(S3Error::Http(404, _)) => return Err(StorageError::NotFound(path)),
+            result => result?,
+        };
+        if response_data.status_code() == 404 {
+            return Err(StorageError::NotFound(path));
+        }
+
+        Ok(response_data.bytes().to_vec())
+    }
+
+    async fn put_file(&self, path: String, data: &[u8]) -> Result<(), StorageError> {
+        self.bucket.put_object(path, data).await?;
+        Ok(())
+    }
+
+    async fn delete_file(&self, path: String) -> Result<(), StorageError> {
+        self.bucket.delete_object(path).await?;
+        Ok(())
+    }
+
+    async fn list(&self, prefix: String) -> Result<Vec<StoredFile>, StorageError> {
+        let results = self.bucket.list(prefix, None).await?;
+        Ok(results
+            .into_iter()
+            .flat_map(|page| page.contents.into_iter())
+            .map(|object| StoredFile {
+                modified: DateTime::parse_from_rfc3339(&object.last_modified)
+                    .ok()
+                    .map(|m| m.with_timezone(&Utc)),
+                path: object.key,
+            })
+            .collect())
+    }
+
+    fn file_url(&self, path: String) -> String {
+        format!("{}/{}/{}", self.endpoint_url, self.bucket.name, path)
+    }
+
+    fn signed_url(&self, path: String) -> Result<String, StorageError> {
+        Ok(self.bucket.presign_get(path, self.url_ttl, None)?)
+    }
+}